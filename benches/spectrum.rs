@@ -0,0 +1,43 @@
+// Compares the cost of one analysis pass with the plain FFT and the multi-resolution spectrum.
+// Run with `cargo bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use ascii_audio_visualizer::audio_processing::{AnalysisSettings, AudioProcessBuffer};
+use ascii_audio_visualizer::spectrum::SpectrumSource;
+
+fn primed_buffer(spectrum_source: SpectrumSource) -> AudioProcessBuffer {
+    let mut buffer = AudioProcessBuffer::new(AnalysisSettings {
+        spectrum_source,
+        ..Default::default()
+    });
+    // a few seconds of a bass tone, a mid tone and some treble
+    for i in 0..(48_000 * 2) {
+        let t = (i as f32) / 48_000.0;
+        let sample = 0.5 * (2.0 * std::f32::consts::PI * 55.0 * t).sin()
+            + 0.3 * (2.0 * std::f32::consts::PI * 440.0 * t).sin()
+            + 0.1 * (2.0 * std::f32::consts::PI * 6_000.0 * t).sin();
+        buffer.push(sample);
+    }
+    buffer
+}
+
+fn spectrum_sources(c: &mut Criterion) {
+    let mut group = c.benchmark_group("process_full_buffer");
+    for (name, source) in [
+        ("fft", SpectrumSource::Fft),
+        ("multi-resolution", SpectrumSource::MultiResolution),
+    ] {
+        let mut buffer = primed_buffer(source);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                buffer.process_full_buffer();
+                black_box(&buffer.features);
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, spectrum_sources);
+criterion_main!(benches);
@@ -0,0 +1,284 @@
+use crate::animators::params::{AnimatorParams, ParamKind, ParamSpec};
+use crate::animators::Animator;
+use crate::audio_processing::{bin_idx_to_freq, AudioFeatures};
+use crate::colors::{lerp, Color};
+use crate::config::Config;
+use crate::surface::Surface;
+
+pub const PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "count",
+        description: "number of bars, 0 fits as many as bar_width and gap allow",
+        kind: ParamKind::Int { min: 0, max: 512 },
+        default: "0",
+    },
+    ParamSpec {
+        name: "bar_width",
+        description: "columns per bar, ignored when count is set",
+        kind: ParamKind::Int { min: 1, max: 64 },
+        default: "2",
+    },
+    ParamSpec {
+        name: "gap",
+        description: "empty columns between bars",
+        kind: ParamKind::Int { min: 0, max: 16 },
+        default: "1",
+    },
+    ParamSpec {
+        name: "min_freq",
+        description: "frequency of the lowest bar in Hz",
+        kind: ParamKind::Float {
+            min: 1.0,
+            max: 24000.0,
+        },
+        default: "50",
+    },
+    ParamSpec {
+        name: "max_freq",
+        description: "frequency of the highest bar in Hz",
+        kind: ParamKind::Float {
+            min: 1.0,
+            max: 24000.0,
+        },
+        default: "10000",
+    },
+    ParamSpec {
+        name: "gain",
+        description: "scales the bar heights",
+        kind: ParamKind::Float {
+            min: 0.01,
+            max: 100.0,
+        },
+        default: "1",
+    },
+    ParamSpec {
+        name: "falloff",
+        description: "how fast bars drop in screen heights per second, 0 drops them at once",
+        kind: ParamKind::Float {
+            min: 0.0,
+            max: 100.0,
+        },
+        default: "2",
+    },
+    ParamSpec {
+        name: "mirror",
+        description: "bars grow up and down from the middle row",
+        kind: ParamKind::Bool,
+        default: "false",
+    },
+    ParamSpec {
+        name: "symmetric",
+        description: "left and right halves reflect each other with the lows in the middle, not a stereo split",
+        kind: ParamKind::Bool,
+        default: "false",
+    },
+    ParamSpec {
+        name: "color",
+        description: "color at the foot of the bars",
+        kind: ParamKind::Color,
+        default: "color_1",
+    },
+    ParamSpec {
+        name: "top_color",
+        description: "color the bars fade to at full height, the same as color for solid bars",
+        kind: ParamKind::Color,
+        default: "color_2",
+    },
+    ParamSpec {
+        name: "background_char",
+        description: "fills the space around the bars",
+        kind: ParamKind::Char,
+        default: "' '",
+    },
+    ParamSpec {
+        name: "background_color",
+        description: "color of background_char",
+        kind: ParamKind::Color,
+        default: "background-alt",
+    },
+];
+
+// Lower eighths of a cell, the last one is a full block
+const EIGHTHS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+// Drawn at the end of a bar growing downwards, there are no upper eighths
+const UPPER_HALF: char = '▀';
+
+// Discrete bars over log spaced frequency bands, with sub-cell heights from the
+// eighth block characters
+#[derive(Default)]
+pub struct Bars {
+    count: usize,
+    bar_width: usize,
+    gap: usize,
+    freq_range: (f32, f32),
+    gain: f32,
+    falloff: f32,
+    mirror: bool,
+    symmetric: bool,
+    color: Color,
+    top_color: Color,
+    background_char: char,
+    background_color: Color,
+    // Shown height of every band as a fraction of the available height, lowest first
+    levels: Vec<f32>,
+    width: usize,
+}
+
+// Where the bars go on a surface
+struct BarLayout {
+    count: usize,
+    bar_width: usize,
+    // Columns left of the first bar, so the bars are centered
+    offset: usize,
+}
+
+impl Bars {
+    fn layout(&self) -> BarLayout {
+        let (width, gap) = (self.width, self.gap);
+        let bar_width = match self.count {
+            0 => self.bar_width,
+            count => ((width + gap) / count).saturating_sub(gap).max(1),
+        };
+        let fits = (width + gap) / (bar_width + gap);
+        let mut count = match self.count {
+            0 => fits,
+            count => count.min(fits),
+        };
+        // both halves get the same number of bars
+        if self.symmetric {
+            count -= count % 2;
+        }
+        let used = (count * (bar_width + gap)).saturating_sub(gap);
+        BarLayout {
+            count,
+            bar_width,
+            offset: width.saturating_sub(used) / 2,
+        }
+    }
+
+    // Symmetric shows every band twice, once on each side of the middle. The analysis is
+    // mono, so both sides are the same rather than the left and right channels.
+    fn bands(&self, bars: usize) -> usize {
+        if self.symmetric {
+            bars / 2
+        } else {
+            bars
+        }
+    }
+
+    fn band_of_bar(&self, bar: usize, bars: usize) -> usize {
+        let half = bars / 2;
+        match (self.symmetric, bar < half) {
+            (true, true) => half - 1 - bar,
+            (true, false) => bar - half,
+            (false, _) => bar,
+        }
+    }
+}
+
+impl Animator for Bars {
+    fn init(&mut self, _config: &Config, params: &AnimatorParams) {
+        self.count = params.int("count") as usize;
+        self.bar_width = params.int("bar_width") as usize;
+        self.gap = params.int("gap") as usize;
+        self.freq_range = (params.float("min_freq"), params.float("max_freq"));
+        self.gain = params.float("gain");
+        self.falloff = params.float("falloff");
+        self.mirror = params.bool("mirror");
+        self.symmetric = params.bool("symmetric");
+        self.color = params.color("color");
+        self.top_color = params.color("top_color");
+        self.background_char = params.char("background_char");
+        self.background_color = params.color("background_color");
+    }
+
+    fn update(&mut self, dt: f32, features: &AudioFeatures) {
+        let bands = self.bands(self.layout().count);
+        let magnitudes: Vec<f32> = features
+            .fft_bins
+            .iter()
+            .map(|bin| bin.smoothed_val)
+            .collect();
+        // magnitude at a fractional bin position, between the neighbouring bins
+        let at = |position: f32| {
+            let position = position.clamp(0.0, (magnitudes.len() - 1) as f32);
+            let below = position.floor() as usize;
+            let above = (below + 1).min(magnitudes.len() - 1);
+            let amount = position - below as f32;
+            magnitudes[below] * (1.0 - amount) + magnitudes[above] * amount
+        };
+
+        // log spaced band edges, as positions in the fft bins
+        let (low, high) = self.freq_range;
+        let bin_width = bin_idx_to_freq(1);
+        let edge = |band: usize| {
+            let freq = low * (high / low).powf(band as f32 / bands.max(1) as f32);
+            freq / bin_width
+        };
+        self.levels.resize(bands, 0.0);
+        for (band, level) in self.levels.iter_mut().enumerate() {
+            let (start, end) = (edge(band), edge(band + 1));
+            // bands narrower than a bin, mostly the lows, are interpolated
+            let magnitude = if end - start < 1.0 {
+                at((start + end) / 2.0)
+            } else {
+                (start.ceil() as usize..end.ceil() as usize)
+                    .map(|bin| at(bin as f32))
+                    .fold(0.0, f32::max)
+            };
+            let target = (magnitude * self.gain).sqrt().min(1.0);
+            *level = if self.falloff > 0.0 {
+                target.max(*level - self.falloff * dt)
+            } else {
+                target
+            };
+        }
+    }
+
+    fn render(&mut self, surface: &mut dyn Surface) {
+        surface.fill(self.background_char, self.background_color);
+        let height = surface.height();
+        let layout = self.layout();
+
+        // bars stand on the bottom row, or on the middle row reaching both ways when mirrored
+        let (base, up, down) = if self.mirror {
+            (height / 2, height / 2, height - height / 2)
+        } else {
+            (height, height, 0)
+        };
+        let color_at = |cells: usize| {
+            let amount = cells as f32 / (up.max(2) - 1) as f32;
+            lerp(self.color, self.top_color, amount.min(1.0))
+        };
+
+        for bar in 0..layout.count {
+            let Some(level) = self.levels.get(self.band_of_bar(bar, layout.count)) else {
+                continue;
+            };
+            let eighths = (level * (up * 8) as f32).round() as usize;
+            let (full, partial) = (eighths / 8, eighths % 8);
+            let x = layout.offset + bar * (layout.bar_width + self.gap);
+            for column in x..x + layout.bar_width {
+                for cells in 0..full.min(up) {
+                    surface.set_cell(EIGHTHS[7], color_at(cells), column, base - 1 - cells);
+                }
+                if partial > 0 && full < up {
+                    let glyph = EIGHTHS[partial - 1];
+                    surface.set_cell(glyph, color_at(full), column, base - 1 - full);
+                }
+
+                // the reflection only has half blocks for the partial cell
+                for cells in 0..full.min(down) {
+                    surface.set_cell(EIGHTHS[7], color_at(cells), column, base + cells);
+                }
+                if partial >= 4 && full < down {
+                    surface.set_cell(UPPER_HALF, color_at(full), column, base + full);
+                }
+            }
+        }
+    }
+
+    fn on_resize(&mut self, width: usize, _height: usize) {
+        self.width = width;
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::VecDeque;
+
+use crate::animators::params::{AnimatorParams, ParamKind, ParamSpec};
+use crate::animators::Animator;
+use crate::audio_processing::AudioFeatures;
+use crate::colors::Color;
+use crate::config::Config;
+use crate::surface::Surface;
+
+pub const PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "lo_char",
+        description: "what the bass band is drawn with",
+        kind: ParamKind::Char,
+        default: "/",
+    },
+    ParamSpec {
+        name: "mi_char",
+        description: "what the mid band is drawn with",
+        kind: ParamKind::Char,
+        default: "\\",
+    },
+    ParamSpec {
+        name: "hi_char",
+        description: "what the treble band is drawn with",
+        kind: ParamKind::Char,
+        default: "/",
+    },
+    ParamSpec {
+        name: "sky_char",
+        description: "fills the space above the mountains",
+        kind: ParamKind::Char,
+        default: "' '",
+    },
+    ParamSpec {
+        name: "dot_spacing",
+        description: "rows between the dots in the sky, 0 leaves it empty",
+        kind: ParamKind::Int { min: 0, max: 100 },
+        default: "3",
+    },
+    ParamSpec {
+        name: "lo_color",
+        description: "color of the bass band",
+        kind: ParamKind::Color,
+        default: "color_1",
+    },
+    ParamSpec {
+        name: "mi_color",
+        description: "color of the mid band",
+        kind: ParamKind::Color,
+        default: "color_2",
+    },
+    ParamSpec {
+        name: "hi_color",
+        description: "color of the treble band",
+        kind: ParamKind::Color,
+        default: "color_3",
+    },
+    ParamSpec {
+        name: "dot_color",
+        description: "color of the dots in the sky",
+        kind: ParamKind::Color,
+        default: "background-alt",
+    },
+];
+
+// Scrolls a landscape of the lo/mi/hi bands from right to left, one column per frame
+#[derive(Default)]
+pub struct EqMountains {
+    // (character, color) of the bands, drawn back to front: hi, mi, lo
+    bands: [(char, Color); 3],
+    sky_char: char,
+    dot_spacing: usize,
+    dot_color: Color,
+    // Band heights as a fraction of the surface height, newest at the back
+    history: VecDeque<[f32; 3]>,
+    width: usize,
+}
+
+impl Animator for EqMountains {
+    fn init(&mut self, _config: &Config, params: &AnimatorParams) {
+        self.bands = [
+            (params.char("hi_char"), params.color("hi_color")),
+            (params.char("mi_char"), params.color("mi_color")),
+            (params.char("lo_char"), params.color("lo_color")),
+        ];
+        self.sky_char = params.char("sky_char");
+        self.dot_spacing = params.int("dot_spacing") as usize;
+        self.dot_color = params.color("dot_color");
+    }
+
+    fn update(&mut self, _dt: f32, features: &AudioFeatures) {
+        let rms = features.root_mean_squared.smoothed_val;
+
+        // scaling lo/mi/hi so they don't overlap
+        let lo = features.lo.smoothed_val * 0.5 * rms;
+        let mi = features.mi.smoothed_val * 0.5 * rms;
+        let hi = features.hi.smoothed_val * 2.0 * rms;
+
+        self.history.push_back([hi, mi, lo]);
+        while self.history.len() > self.width.max(1) {
+            self.history.pop_front();
+        }
+    }
+
+    fn render(&mut self, surface: &mut dyn Surface) {
+        let height = surface.height();
+        let bg = surface.bg_color();
+        fn char_height(num: f32, max_height: usize) -> i32 {
+            ((num * (max_height as f32)) as i32).min(max_height as i32)
+        }
+
+        // columns without history yet stay flat on the left
+        let offset = surface.width().saturating_sub(self.history.len());
+        for x in 0..surface.width() {
+            surface.draw_line_v(self.sky_char, bg, x, 0, height as i32);
+            if let Some(levels) = x.checked_sub(offset).map(|i| self.history[i]) {
+                for ((c, color), level) in self.bands.iter().zip(levels) {
+                    surface.draw_line_v(*c, *color, x, height, -char_height(level, height));
+                }
+            }
+
+            if self.dot_spacing == 0 {
+                continue;
+            }
+            for j in (0..height).step_by(self.dot_spacing) {
+                if surface.get_cell(x, j).c == self.sky_char {
+                    surface.set_cell('.', self.dot_color, x, j);
+                }
+            }
+        }
+    }
+
+    fn on_resize(&mut self, width: usize, _height: usize) {
+        self.width = width;
+        while self.history.len() > width {
+            self.history.pop_front();
+        }
+    }
+
+    // Start from a flat landscape each time the animation comes around
+    fn on_enter(&mut self) {
+        self.history.clear();
+    }
+}
@@ -0,0 +1,239 @@
+use crate::audio_processing::AudioFeatures;
+use crate::compositor::Compositor;
+use crate::config::{Config, IdleScreen};
+use crate::input::Sources;
+use crate::surface::Surface;
+
+pub mod params;
+
+pub mod bars;
+pub mod eq_mountains;
+pub mod oscilloscope;
+pub mod sine_like;
+pub mod spectrogram;
+pub mod spectrum;
+pub mod wiggly;
+
+use bars::Bars;
+use eq_mountains::EqMountains;
+use oscilloscope::Oscilloscope;
+use params::{AnimatorParams, ParamSpec};
+use sine_like::SineLike;
+use spectrogram::Spectrogram;
+use spectrum::Spectrum;
+use wiggly::Wiggly;
+
+// An animation that keeps its own state between frames. Every frame the visible
+// animator is advanced with update() and then drawn with render().
+pub trait Animator {
+    // Called once after construction, before any other method, with the
+    // validated settings of the animator's [animation.<name>] section
+    fn init(&mut self, config: &Config, params: &AnimatorParams);
+    // Advances the animation by `dt` seconds using the latest features
+    fn update(&mut self, dt: f32, features: &AudioFeatures);
+    fn render(&mut self, surface: &mut dyn Surface);
+    // Called before the first render and whenever the surface changes size
+    fn on_resize(&mut self, _width: usize, _height: usize) {}
+    // The animator is about to be shown, or was just replaced by another one
+    fn on_enter(&mut self) {}
+    fn on_exit(&mut self) {}
+    // Called once the inputs are connected, for animators that read sources themselves
+    fn attach_sources(&mut self, _sources: &Sources) {}
+}
+
+// An animator from the rotation, with the source and filtered view it reads
+// (None for the default source and its unfiltered signal)
+pub struct Animation {
+    pub animator: Box<dyn Animator>,
+    pub source: Option<String>,
+    pub view: Option<String>,
+}
+
+impl Animation {
+    pub fn new(name: &str, config: &Config) -> Animation {
+        Animation {
+            animator: create_animator(name, config),
+            source: config.animation_sources.get(name).cloned(),
+            view: config.animation_views.get(name).cloned(),
+        }
+    }
+}
+
+pub struct Animators {
+    pub list: Vec<Animation>,
+}
+
+impl Animators {
+    pub fn new(config: &Config) -> Animators {
+        let animators: Vec<Animation> = config
+            .animations
+            .iter()
+            .map(|name| Animation::new(name, config))
+            .collect();
+        Animators { list: animators }
+    }
+
+    // Shown instead of the rotation while the input is silent, None dims the last frame
+    pub fn idle(config: &Config) -> Option<Box<dyn Animator>> {
+        match &config.idle_screen {
+            IdleScreen::Dim => None,
+            IdleScreen::Animation(name) => Some(create_animator(name, config)),
+        }
+    }
+
+    // Only the named animation, for a region that doesn't follow the rotation
+    pub fn fixed(config: &Config, name: &str) -> Animators {
+        Animators {
+            list: vec![Animation::new(name, config)],
+        }
+    }
+
+    pub fn attach_sources(&mut self, sources: &Sources) {
+        for animation in self.list.iter_mut() {
+            animation.animator.attach_sources(sources);
+        }
+    }
+
+    // Tells every animator in the rotation about a new surface size
+    pub fn resize(&mut self, width: usize, height: usize) {
+        for animation in self.list.iter_mut() {
+            animation.animator.on_resize(width, height);
+        }
+    }
+}
+
+// Catalogue entry of an animator, used to create it from its config name, to check
+// its config section and for --list-animations
+pub struct AnimatorInfo {
+    pub name: &'static str,
+    // Other names accepted in the config
+    pub aliases: &'static [&'static str],
+    pub description: &'static str,
+    pub params: &'static [ParamSpec],
+    create: fn() -> Box<dyn Animator>,
+}
+
+pub const REGISTRY: &[AnimatorInfo] = &[
+    AnimatorInfo {
+        name: "sine_like",
+        aliases: &["sine-like", "sine"],
+        description: "Nested waves sized by loudness, denser with more high frequency content",
+        params: sine_like::PARAMS,
+        create: || Box::<SineLike>::default(),
+    },
+    AnimatorInfo {
+        name: "spectrum",
+        aliases: &[],
+        description: "Log frequency spectrum from 12Hz to 10kHz as bars",
+        params: spectrum::PARAMS,
+        create: || Box::<Spectrum>::default(),
+    },
+    AnimatorInfo {
+        name: "wiggly",
+        aliases: &[],
+        description: "Moving interference pattern that lights up with loudness",
+        params: wiggly::PARAMS,
+        create: || Box::<Wiggly>::default(),
+    },
+    AnimatorInfo {
+        name: "eq_mountains",
+        aliases: &["eq-mountains", "mountains"],
+        description: "Bass, mid and treble levels scrolling from right to left",
+        params: eq_mountains::PARAMS,
+        create: || Box::<EqMountains>::default(),
+    },
+    AnimatorInfo {
+        name: "bars",
+        aliases: &["cava"],
+        description:
+            "Spectrum as separate bars with sub-cell heights, optionally mirrored or symmetric",
+        params: bars::PARAMS,
+        create: || Box::<Bars>::default(),
+    },
+    AnimatorInfo {
+        name: "oscilloscope",
+        aliases: &["scope", "waveform"],
+        description:
+            "The waveform itself, triggered on rising zero crossings, with phosphor trails",
+        params: oscilloscope::PARAMS,
+        create: || Box::<Oscilloscope>::default(),
+    },
+    AnimatorInfo {
+        name: "spectrogram",
+        aliases: &["waterfall"],
+        description: "Log frequency spectrum over time, scrolling sideways or down",
+        params: spectrogram::PARAMS,
+        create: || Box::<Spectrogram>::default(),
+    },
+];
+
+// Looks up an animator by name or alias, the error suggests close matches
+pub fn find_animator(name: &str) -> Result<&'static AnimatorInfo, String> {
+    REGISTRY
+        .iter()
+        .find(|info| info.name == name || info.aliases.contains(&name))
+        .ok_or_else(|| {
+            let mut suggestions: Vec<(f64, &str)> = REGISTRY
+                .iter()
+                .filter_map(|info| {
+                    let similarity = std::iter::once(&info.name)
+                        .chain(info.aliases.iter())
+                        .map(|known| strsim::normalized_damerau_levenshtein(name, known))
+                        .fold(0.0, f64::max);
+                    (similarity >= SUGGESTION_SIMILARITY).then_some((similarity, info.name))
+                })
+                .collect();
+            suggestions.sort_by(|a, b| b.0.total_cmp(&a.0));
+            let names = |names: Vec<&str>| names.join("', '");
+            if suggestions.is_empty() {
+                format!(
+                    "unknown animation '{name}', expected one of '{}'.",
+                    names(REGISTRY.iter().map(|info| info.name).collect())
+                )
+            } else {
+                format!(
+                    "unknown animation '{name}', did you mean '{}'?",
+                    names(suggestions.iter().map(|(_, name)| *name).collect())
+                )
+            }
+        })
+}
+
+// Names closer than this to a known animation are suggested when a lookup fails
+const SUGGESTION_SIMILARITY: f64 = 0.5;
+
+fn create_animator(animator_name: &str, config: &Config) -> Box<dyn Animator> {
+    if let Some(layers) = config.layers.get(animator_name) {
+        let layers = layers
+            .iter()
+            .map(|(name, blend)| (Animation::new(name, config), *blend))
+            .collect();
+        return Box::new(Compositor::new(config, layers));
+    }
+    let info = find_animator(animator_name).unwrap_or_else(|err| panic!("Error: {err}"));
+    let params = &config.animation_params[info.name];
+    let mut animator = (info.create)();
+    animator.init(config, params);
+    animator
+}
+
+// Prints the catalogue for --list-animations
+pub fn list_animations() {
+    for info in REGISTRY {
+        if info.aliases.is_empty() {
+            println!("{}", info.name);
+        } else {
+            println!("{} (also: {})", info.name, info.aliases.join(", "));
+        }
+        println!("    {}", info.description);
+        if !info.params.is_empty() {
+            println!("    [animation.{}]", info.name);
+        }
+        for param in info.params {
+            println!(
+                "    {} = {} ; {}, {}",
+                param.name, param.default, param.kind, param.description
+            );
+        }
+    }
+}
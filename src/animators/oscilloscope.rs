@@ -0,0 +1,218 @@
+use crate::animators::params::{AnimatorParams, ParamKind, ParamSpec};
+use crate::animators::Animator;
+use crate::audio_processing::{AudioFeatures, WAVEFORM_SIZE};
+use crate::colors::{lerp, Color};
+use crate::config::Config;
+use crate::surface::Surface;
+
+pub const PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "style",
+        description: "line joins the samples, dots marks them, filled shades to the center",
+        kind: ParamKind::Choice(&["line", "dots", "filled"]),
+        default: "line",
+    },
+    ParamSpec {
+        name: "char",
+        description: "what the trace is drawn with",
+        kind: ParamKind::Char,
+        default: "*",
+    },
+    ParamSpec {
+        name: "window",
+        description: "samples across the screen, at 48kHz",
+        kind: ParamKind::Int {
+            min: 16,
+            max: WAVEFORM_SIZE as i64,
+        },
+        default: "400",
+    },
+    ParamSpec {
+        name: "trigger",
+        description: "start the trace at a rising zero crossing so periodic sounds stand still",
+        kind: ParamKind::Bool,
+        default: "true",
+    },
+    ParamSpec {
+        name: "gain",
+        description: "scales the waveform, samples beyond the edges are clipped",
+        kind: ParamKind::Float {
+            min: 0.01,
+            max: 100.0,
+        },
+        default: "2",
+    },
+    ParamSpec {
+        name: "persistence",
+        description: "seconds old traces take to fade like phosphor, 0 shows only the newest",
+        kind: ParamKind::Float {
+            min: 0.0,
+            max: 10.0,
+        },
+        default: "0.25",
+    },
+    ParamSpec {
+        name: "color",
+        description: "color of the newest trace",
+        kind: ParamKind::Color,
+        default: "color_3",
+    },
+    ParamSpec {
+        name: "fade_color",
+        description: "color old traces fade to before they disappear",
+        kind: ParamKind::Color,
+        default: "color_1",
+    },
+    ParamSpec {
+        name: "background_char",
+        description: "fills the space around the trace",
+        kind: ParamKind::Char,
+        default: "' '",
+    },
+    ParamSpec {
+        name: "background_color",
+        description: "color of background_char",
+        kind: ParamKind::Color,
+        default: "background-alt",
+    },
+];
+
+// Traces dimmer than this are gone
+const MIN_GLOW: f32 = 0.05;
+
+#[derive(Default)]
+enum Style {
+    #[default]
+    Line,
+    Dots,
+    Filled,
+}
+
+// The analysed waveform, like a scope triggered on rising zero crossings. Cells the
+// trace passed keep glowing and fade out over the next frames.
+#[derive(Default)]
+pub struct Oscilloscope {
+    style: Style,
+    trace_char: char,
+    window: usize,
+    trigger: bool,
+    gain: f32,
+    persistence: f32,
+    color: Color,
+    fade_color: Color,
+    background_char: char,
+    background_color: Color,
+    waveform: Vec<f32>,
+    // Brightness of every cell, row by row, 1.0 where the newest trace is
+    glow: Vec<f32>,
+    width: usize,
+    height: usize,
+}
+
+impl Oscilloscope {
+    // First sample of the window, after the first rising zero crossing that leaves a full window
+    fn trigger_point(&self) -> usize {
+        let last_start = self.waveform.len().saturating_sub(self.window);
+        if !self.trigger {
+            return last_start;
+        }
+        (1..=last_start)
+            .find(|&i| self.waveform[i - 1] < 0.0 && self.waveform[i] >= 0.0)
+            .unwrap_or(last_start)
+    }
+
+    // Row of a sample, +1.0 at the top
+    fn row(&self, sample: f32) -> usize {
+        let sample = (sample * self.gain).clamp(-1.0, 1.0);
+        ((1.0 - sample) / 2.0 * (self.height.saturating_sub(1)) as f32).round() as usize
+    }
+
+    fn light(&mut self, x: usize, from: usize, to: usize) {
+        for y in from.min(to)..=from.max(to) {
+            self.glow[y * self.width + x] = 1.0;
+        }
+    }
+}
+
+impl Animator for Oscilloscope {
+    fn init(&mut self, _config: &Config, params: &AnimatorParams) {
+        self.style = match params.choice("style") {
+            "dots" => Style::Dots,
+            "filled" => Style::Filled,
+            _ => Style::Line,
+        };
+        self.trace_char = params.char("char");
+        self.window = params.int("window") as usize;
+        self.trigger = params.bool("trigger");
+        self.gain = params.float("gain");
+        self.persistence = params.float("persistence");
+        self.color = params.color("color");
+        self.fade_color = params.color("fade_color");
+        self.background_char = params.char("background_char");
+        self.background_color = params.color("background_color");
+    }
+
+    fn update(&mut self, dt: f32, features: &AudioFeatures) {
+        self.waveform.clear();
+        self.waveform.extend_from_slice(&features.waveform);
+
+        let fade = if self.persistence > 0.0 {
+            (-dt / self.persistence).exp()
+        } else {
+            0.0
+        };
+        for glow in self.glow.iter_mut() {
+            *glow *= fade;
+        }
+    }
+
+    fn render(&mut self, surface: &mut dyn Surface) {
+        surface.fill(self.background_char, self.background_color);
+        if self.width == 0 || self.height == 0 || self.waveform.is_empty() {
+            return;
+        }
+
+        // every column covers the samples up to the next one, the line style
+        // spans all of them so steep edges stay connected
+        let start = self.trigger_point();
+        let window = self.window.min(self.waveform.len() - start);
+        let center = self.row(0.0);
+        for x in 0..self.width {
+            let first = start + x * window / self.width;
+            let last = (start + (x + 1) * window / self.width).clamp(first, start + window - 1);
+            let row = self.row(self.waveform[first]);
+            match self.style {
+                Style::Line => {
+                    let rows = self.waveform[first..=last].iter().map(|s| self.row(*s));
+                    let (top, bottom) = rows.fold((row, row), |(top, bottom), row| {
+                        (top.min(row), bottom.max(row))
+                    });
+                    self.light(x, top, bottom);
+                }
+                Style::Dots => self.light(x, row, row),
+                Style::Filled => self.light(x, center, row),
+            }
+        }
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let glow = self.glow[y * self.width + x];
+                if glow >= MIN_GLOW {
+                    let color = lerp(self.fade_color, self.color, glow);
+                    surface.set_cell(self.trace_char, color, x, y);
+                }
+            }
+        }
+    }
+
+    fn on_resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.glow = vec![0.0; width * height];
+    }
+
+    // Old traces from the last time around shouldn't flash up
+    fn on_enter(&mut self) {
+        self.glow.fill(0.0);
+    }
+}
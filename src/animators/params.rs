@@ -0,0 +1,202 @@
+// Typed settings of an animator, read from its [animation.<name>] section.
+// Every animator declares its parameters in the registry, values are checked against
+// the declared type and range when the config is loaded.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::colors::{Color, FromHex, Palette};
+use crate::surface::NO_CELL;
+
+#[derive(Copy, Clone)]
+pub enum ParamKind {
+    // A single character, e.g. what a bar is drawn with, or 'none' to leave
+    // the cell empty so layers below show through
+    Char,
+    // 0xRRGGBB or one of the [colors] names
+    Color,
+    Float { min: f32, max: f32 },
+    Int { min: i64, max: i64 },
+    Bool,
+    // One of a fixed set of words
+    Choice(&'static [&'static str]),
+}
+
+impl fmt::Display for ParamKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamKind::Char => write!(f, "character or none"),
+            ParamKind::Color => write!(f, "color"),
+            ParamKind::Float { min, max } => write!(f, "number {min}..{max}"),
+            ParamKind::Int { min, max } => write!(f, "integer {min}..{max}"),
+            ParamKind::Bool => write!(f, "true/false"),
+            ParamKind::Choice(choices) => write!(f, "{}", choices.join("/")),
+        }
+    }
+}
+
+// A setting an animator reads from its config section
+pub struct ParamSpec {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub kind: ParamKind,
+    // Used when the section doesn't set it, parsed like a config value
+    pub default: &'static str,
+}
+
+#[derive(Clone, Debug)]
+pub enum ParamValue {
+    Char(char),
+    Color(Color),
+    Float(f32),
+    Int(i64),
+    Bool(bool),
+    Choice(String),
+}
+
+impl ParamKind {
+    fn parse(&self, value: &str, palette: &Palette) -> Result<ParamValue, String> {
+        match self {
+            ParamKind::Char if value == "none" => Ok(ParamValue::Char(NO_CELL)),
+            ParamKind::Char => {
+                // quotes allow characters the config would strip, e.g. ' '
+                let unquoted = ['\'', '"']
+                    .iter()
+                    .find_map(|quote| value.strip_prefix(*quote)?.strip_suffix(*quote))
+                    .filter(|unquoted| !unquoted.is_empty());
+                let mut chars = unquoted.unwrap_or(value).chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Ok(ParamValue::Char(c)),
+                    _ => Err(format!("expected a single character, got '{value}'")),
+                }
+            }
+            ParamKind::Color => palette
+                .named(value)
+                .map_or_else(|| Color::from_hex_string(value.to_string()), Ok)
+                .map(ParamValue::Color),
+            ParamKind::Float { min, max } => {
+                let number = value
+                    .parse::<f32>()
+                    .map_err(|_| format!("expected a number, got '{value}'"))?;
+                if number < *min || number > *max {
+                    return Err(format!("{number} is outside {min}..{max}"));
+                }
+                Ok(ParamValue::Float(number))
+            }
+            ParamKind::Int { min, max } => {
+                let number = value
+                    .parse::<i64>()
+                    .map_err(|_| format!("expected an integer, got '{value}'"))?;
+                if number < *min || number > *max {
+                    return Err(format!("{number} is outside {min}..{max}"));
+                }
+                Ok(ParamValue::Int(number))
+            }
+            ParamKind::Bool => match value {
+                "true" | "yes" | "on" | "1" => Ok(ParamValue::Bool(true)),
+                "false" | "no" | "off" | "0" => Ok(ParamValue::Bool(false)),
+                _ => Err(format!("expected true or false, got '{value}'")),
+            },
+            ParamKind::Choice(choices) if choices.contains(&value) => {
+                Ok(ParamValue::Choice(value.to_string()))
+            }
+            ParamKind::Choice(choices) => Err(format!(
+                "expected one of {}, got '{value}'",
+                choices.join(", ")
+            )),
+        }
+    }
+}
+
+// The values of every declared parameter, defaults filled in
+#[derive(Clone, Default)]
+pub struct AnimatorParams {
+    values: HashMap<&'static str, ParamValue>,
+}
+
+impl AnimatorParams {
+    // Checks a config section against the declared parameters, `section` is None without one
+    pub fn parse(
+        specs: &'static [ParamSpec],
+        section: Option<&HashMap<String, Option<String>>>,
+        palette: &Palette,
+    ) -> Result<AnimatorParams, String> {
+        if let Some(section) = section {
+            for key in section.keys() {
+                if !specs.iter().any(|spec| spec.name == key) {
+                    let names: Vec<&str> = specs.iter().map(|spec| spec.name).collect();
+                    if names.is_empty() {
+                        return Err(format!("unknown setting '{key}', it has no settings"));
+                    }
+                    return Err(format!(
+                        "unknown setting '{key}', expected one of {}",
+                        names.join(", ")
+                    ));
+                }
+            }
+        }
+
+        let mut values = HashMap::new();
+        for spec in specs {
+            let value = section
+                .and_then(|section| section.get(spec.name))
+                .and_then(|value| value.as_deref())
+                .filter(|value| !value.is_empty())
+                .unwrap_or(spec.default);
+            let value = spec
+                .kind
+                .parse(value, palette)
+                .map_err(|err| format!("'{}': {err}", spec.name))?;
+            values.insert(spec.name, value);
+        }
+        Ok(AnimatorParams { values })
+    }
+
+    fn get(&self, name: &str) -> &ParamValue {
+        self.values
+            .get(name)
+            .unwrap_or_else(|| panic!("animation parameter '{name}' is not declared"))
+    }
+
+    pub fn char(&self, name: &str) -> char {
+        match self.get(name) {
+            ParamValue::Char(c) => *c,
+            value => panic!("animation parameter '{name}' is not a character: {value:?}"),
+        }
+    }
+
+    pub fn color(&self, name: &str) -> Color {
+        match self.get(name) {
+            ParamValue::Color(color) => *color,
+            value => panic!("animation parameter '{name}' is not a color: {value:?}"),
+        }
+    }
+
+    pub fn float(&self, name: &str) -> f32 {
+        match self.get(name) {
+            ParamValue::Float(number) => *number,
+            value => panic!("animation parameter '{name}' is not a number: {value:?}"),
+        }
+    }
+
+    pub fn int(&self, name: &str) -> i64 {
+        match self.get(name) {
+            ParamValue::Int(number) => *number,
+            value => panic!("animation parameter '{name}' is not an integer: {value:?}"),
+        }
+    }
+
+    pub fn bool(&self, name: &str) -> bool {
+        match self.get(name) {
+            ParamValue::Bool(flag) => *flag,
+            value => panic!("animation parameter '{name}' is not true/false: {value:?}"),
+        }
+    }
+
+    pub fn choice(&self, name: &str) -> &str {
+        match self.get(name) {
+            ParamValue::Choice(choice) => choice,
+            value => panic!("animation parameter '{name}' is not a choice: {value:?}"),
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use crate::animators::params::{AnimatorParams, ParamKind, ParamSpec};
+use crate::animators::Animator;
+use crate::audio_processing::AudioFeatures;
+use crate::colors::Color;
+use crate::config::Config;
+use crate::surface::Surface;
+
+pub const PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "char",
+        description: "what the waves are drawn with",
+        kind: ParamKind::Char,
+        default: "*",
+    },
+    ParamSpec {
+        name: "background_char",
+        description: "fills the space around the waves",
+        kind: ParamKind::Char,
+        default: ".",
+    },
+    ParamSpec {
+        name: "gain",
+        description: "wave height per squared loudness",
+        kind: ParamKind::Float {
+            min: 0.0,
+            max: 1000.0,
+        },
+        default: "15.0",
+    },
+    ParamSpec {
+        name: "outer_color",
+        description: "color of the largest wave",
+        kind: ParamKind::Color,
+        default: "color_1",
+    },
+    ParamSpec {
+        name: "middle_color",
+        description: "color of the middle wave",
+        kind: ParamKind::Color,
+        default: "color_2",
+    },
+    ParamSpec {
+        name: "inner_color",
+        description: "color of the smallest wave",
+        kind: ParamKind::Color,
+        default: "color_3",
+    },
+    ParamSpec {
+        name: "background_color",
+        description: "color of background_char",
+        kind: ParamKind::Color,
+        default: "background-alt",
+    },
+];
+
+#[derive(Default)]
+pub struct SineLike {
+    wave_char: char,
+    background_char: char,
+    gain: f32,
+    colors: [Color; 3],
+    background_color: Color,
+    rms: f32,
+    zcr: f32,
+}
+
+impl Animator for SineLike {
+    fn init(&mut self, _config: &Config, params: &AnimatorParams) {
+        self.wave_char = params.char("char");
+        self.background_char = params.char("background_char");
+        self.gain = params.float("gain");
+        self.colors = [
+            params.color("outer_color"),
+            params.color("middle_color"),
+            params.color("inner_color"),
+        ];
+        self.background_color = params.color("background_color");
+    }
+
+    fn update(&mut self, _dt: f32, features: &AudioFeatures) {
+        self.rms = features.root_mean_squared.smoothed_val;
+        self.zcr = features.zero_crossing_rate.smoothed_val;
+    }
+
+    fn render(&mut self, surface: &mut dyn Surface) {
+        let (width, height) = (surface.width(), surface.height());
+        let center_idx = (height / 2) as i32;
+
+        // fill background
+        for x in 0..width {
+            surface.draw_line_v(
+                self.background_char,
+                self.background_color,
+                x,
+                0,
+                height as i32,
+            );
+        }
+
+        // draw waves
+        for x in 0..width {
+            let mut x_position = (x as f32) / (width as f32);
+            x_position *= (self.zcr + 0.01) * 188.0 * (height as f32);
+            x_position = (x_position * 0.03) + 0.8;
+
+            // sin output is rescaled from [-1,1] to [0,1]
+            let mut sin_out = (x_position.sin() + 1.0) / 2.0;
+            sin_out = sin_out * (height as f32) * 0.028;
+            sin_out = self.gain * self.rms * self.rms * (sin_out * 0.80 + 0.2) + 0.5;
+
+            // draw waves, each half the size of the previous one
+            let wave_size = 2 * (sin_out as i32).min(center_idx);
+            for (i, color) in self.colors.iter().enumerate() {
+                let size = wave_size >> i;
+                surface.draw_line_v(
+                    self.wave_char,
+                    *color,
+                    x,
+                    (center_idx - size / 2) as usize,
+                    size,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::VecDeque;
+
+use crate::animators::params::{AnimatorParams, ParamKind, ParamSpec};
+use crate::animators::Animator;
+use crate::audio_processing::AudioFeatures;
+use crate::colors::{lerp, Color};
+use crate::config::Config;
+use crate::surface::Surface;
+
+pub const PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "scroll",
+        description: "horizontal runs time right to left, vertical runs it top to bottom",
+        kind: ParamKind::Choice(&["horizontal", "vertical"]),
+        default: "horizontal",
+    },
+    ParamSpec {
+        name: "speed",
+        description: "columns (or rows) scrolled per second",
+        kind: ParamKind::Float {
+            min: 0.1,
+            max: 240.0,
+        },
+        default: "30",
+    },
+    ParamSpec {
+        name: "min_freq",
+        description: "lowest frequency shown in Hz",
+        kind: ParamKind::Float {
+            min: 1.0,
+            max: 24000.0,
+        },
+        default: "40",
+    },
+    ParamSpec {
+        name: "max_freq",
+        description: "highest frequency shown in Hz",
+        kind: ParamKind::Float {
+            min: 1.0,
+            max: 24000.0,
+        },
+        default: "16000",
+    },
+    ParamSpec {
+        name: "gain",
+        description: "scales the magnitudes before they are colored",
+        kind: ParamKind::Float {
+            min: 0.01,
+            max: 100.0,
+        },
+        default: "1",
+    },
+    ParamSpec {
+        name: "char",
+        description: "what every audible cell is drawn with",
+        kind: ParamKind::Char,
+        default: "█",
+    },
+    ParamSpec {
+        name: "low_color",
+        description: "start of the color ramp, for quiet cells",
+        kind: ParamKind::Color,
+        default: "background-alt",
+    },
+    ParamSpec {
+        name: "mid_color",
+        description: "middle of the color ramp",
+        kind: ParamKind::Color,
+        default: "color_1",
+    },
+    ParamSpec {
+        name: "high_color",
+        description: "end of the color ramp, for the loudest cells",
+        kind: ParamKind::Color,
+        default: "color_2",
+    },
+    ParamSpec {
+        name: "background_char",
+        description: "drawn where there is next to nothing",
+        kind: ParamKind::Char,
+        default: "' '",
+    },
+    ParamSpec {
+        name: "axis",
+        description: "label frequencies along the left edge, or the top when vertical",
+        kind: ParamKind::Bool,
+        default: "false",
+    },
+    ParamSpec {
+        name: "axis_color",
+        description: "color of the frequency labels",
+        kind: ParamKind::Color,
+        default: "color_3",
+    },
+];
+
+// Levels under this are drawn as background_char
+const MIN_LEVEL: f32 = 0.05;
+// Frequencies labeled on the axis, when they are in range and there is room
+const AXIS_FREQS: [f32; 10] = [
+    20.0, 50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0, 10000.0, 20000.0,
+];
+// Columns kept for the labels with horizontal scrolling, e.g. "10k "
+const AXIS_WIDTH: usize = 4;
+
+// Scrolling history of the spectrum, one column (or row) per time step, with the
+// frequency bands log spaced along the other direction
+#[derive(Default)]
+pub struct Spectrogram {
+    vertical: bool,
+    speed: f32,
+    freq_range: (f32, f32),
+    gain: f32,
+    cell_char: char,
+    ramp: [Color; 3],
+    background_char: char,
+    axis: bool,
+    axis_color: Color,
+    // Levels of every band, lowest band first, newest line at the back
+    history: VecDeque<Vec<f32>>,
+    // Time steps owed to the history, the fraction carries over to the next update
+    pending: f32,
+    width: usize,
+    height: usize,
+}
+
+impl Spectrogram {
+    // (bands, lines of history) that fit next to the axis
+    fn extent(&self) -> (usize, usize) {
+        match (self.vertical, self.axis) {
+            (false, false) => (self.height, self.width),
+            (false, true) => (self.height, self.width.saturating_sub(AXIS_WIDTH)),
+            (true, false) => (self.width, self.height),
+            (true, true) => (self.width, self.height.saturating_sub(1)),
+        }
+    }
+
+    // Position of a frequency along the bands, 0.0 at min_freq
+    fn band_position(&self, freq: f32, bands: usize) -> f32 {
+        let (low, high) = self.freq_range;
+        (freq / low).ln() / (high / low).ln() * bands as f32
+    }
+
+    fn levels(&self, features: &AudioFeatures, bands: usize) -> Vec<f32> {
+        let (low, high) = self.freq_range;
+        let edge = |band: usize| low * (high / low).powf(band as f32 / bands as f32);
+        let spectrum = features
+            .spectrum
+            .iter()
+            .zip(features.spectrum_freqs.iter())
+            .map(|(value, freq)| (*freq, value.smoothed_val));
+
+        (0..bands)
+            .map(|band| {
+                let (start, end) = (edge(band), edge(band + 1));
+                let in_band = spectrum
+                    .clone()
+                    .filter(|(freq, _)| start <= *freq && *freq < end)
+                    .map(|(_, magnitude)| magnitude)
+                    .reduce(f32::max);
+                // bands narrower than the spectrum's resolution take the closest value
+                let magnitude = in_band.unwrap_or_else(|| {
+                    let center = (start * end).sqrt();
+                    spectrum
+                        .clone()
+                        .min_by(|a, b| (a.0 - center).abs().total_cmp(&(b.0 - center).abs()))
+                        .map_or(0.0, |(_, magnitude)| magnitude)
+                });
+                (magnitude * self.gain).sqrt().min(1.0)
+            })
+            .collect()
+    }
+
+    fn color(&self, level: f32) -> Color {
+        let [low, mid, high] = self.ramp;
+        if level < 0.5 {
+            lerp(low, mid, level * 2.0)
+        } else {
+            lerp(mid, high, level * 2.0 - 1.0)
+        }
+    }
+
+    fn draw_axis(&self, surface: &mut dyn Surface, bands: usize) {
+        if bands == 0 || self.height == 0 {
+            return;
+        }
+        // the last label's far end, so labels never touch
+        let mut taken: Option<usize> = None;
+        for freq in AXIS_FREQS {
+            let (low, high) = self.freq_range;
+            if freq < low || freq > high {
+                continue;
+            }
+            let label = axis_label(freq);
+            let band = (self.band_position(freq, bands) as usize).min(bands.saturating_sub(1));
+            if self.vertical {
+                if taken.is_some_and(|end| band <= end) {
+                    continue;
+                }
+                surface.draw_text(&label, self.axis_color, band, 0);
+                taken = Some(band + label.len());
+            } else {
+                if taken.is_some_and(|end| band <= end + 1) {
+                    continue;
+                }
+                surface.draw_text(&label, self.axis_color, 0, self.height - 1 - band);
+                taken = Some(band);
+            }
+        }
+    }
+}
+
+impl Animator for Spectrogram {
+    fn init(&mut self, _config: &Config, params: &AnimatorParams) {
+        self.vertical = params.choice("scroll") == "vertical";
+        self.speed = params.float("speed");
+        self.freq_range = (params.float("min_freq"), params.float("max_freq"));
+        self.gain = params.float("gain");
+        self.cell_char = params.char("char");
+        self.ramp = [
+            params.color("low_color"),
+            params.color("mid_color"),
+            params.color("high_color"),
+        ];
+        self.background_char = params.char("background_char");
+        self.axis = params.bool("axis");
+        self.axis_color = params.color("axis_color");
+    }
+
+    fn update(&mut self, dt: f32, features: &AudioFeatures) {
+        let (bands, lines) = self.extent();
+        self.pending += dt * self.speed;
+        let steps = self.pending as usize;
+        self.pending -= steps as f32;
+        if steps == 0 || bands == 0 {
+            return;
+        }
+
+        // every step of this update saw the same features
+        let levels = self.levels(features, bands);
+        for _ in 0..steps.min(lines) {
+            self.history.push_back(levels.clone());
+        }
+        while self.history.len() > lines {
+            self.history.pop_front();
+        }
+    }
+
+    fn render(&mut self, surface: &mut dyn Surface) {
+        let background = self.ramp[0];
+        surface.fill(self.background_char, background);
+        let (bands, lines) = self.extent();
+        // nothing fits, e.g. a region squeezed to nothing
+        if bands == 0 || lines == 0 {
+            return;
+        }
+
+        // the newest line is at the right edge, or the top when vertical
+        let first_line = lines.saturating_sub(self.history.len());
+        for (i, levels) in self.history.iter().enumerate() {
+            let line = first_line + i;
+            for (band, level) in levels.iter().enumerate() {
+                if *level < MIN_LEVEL {
+                    continue;
+                }
+                let (x, y) = if self.vertical {
+                    (band, self.height - 1 - line)
+                } else {
+                    (self.width - lines + line, self.height - 1 - band)
+                };
+                surface.set_cell(self.cell_char, self.color(*level), x, y);
+            }
+        }
+        if self.axis {
+            self.draw_axis(surface, bands);
+        }
+    }
+
+    fn on_resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        // the bands no longer line up with the surface
+        self.history.clear();
+    }
+}
+
+// e.g. "50", "500", "2k"
+fn axis_label(freq: f32) -> String {
+    if freq >= 1000.0 {
+        format!("{}k", (freq / 1000.0).round())
+    } else {
+        format!("{}", freq.round())
+    }
+}
@@ -0,0 +1,158 @@
+use crate::animators::params::{AnimatorParams, ParamKind, ParamSpec};
+use crate::animators::Animator;
+use crate::audio_processing::AudioFeatures;
+use crate::colors::Color;
+use crate::config::Config;
+use crate::surface::Surface;
+
+pub const PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "char",
+        description: "what the bars are drawn with",
+        kind: ParamKind::Char,
+        default: "=",
+    },
+    ParamSpec {
+        name: "background_char",
+        description: "fills the space above the bars",
+        kind: ParamKind::Char,
+        default: ".",
+    },
+    ParamSpec {
+        name: "bar_width",
+        description: "columns per bar",
+        kind: ParamKind::Int { min: 1, max: 64 },
+        default: "1",
+    },
+    ParamSpec {
+        name: "decay",
+        description: "how much of a bar carries over into empty neighbours",
+        kind: ParamKind::Float { min: 0.0, max: 1.0 },
+        default: "0.7",
+    },
+    ParamSpec {
+        name: "min_freq",
+        description: "frequency of the leftmost bar in Hz",
+        kind: ParamKind::Float {
+            min: 1.0,
+            max: 24000.0,
+        },
+        default: "12",
+    },
+    ParamSpec {
+        name: "max_freq",
+        description: "frequency of the rightmost bar in Hz",
+        kind: ParamKind::Float {
+            min: 1.0,
+            max: 24000.0,
+        },
+        default: "10000",
+    },
+    ParamSpec {
+        name: "color",
+        description: "color of the bars",
+        kind: ParamKind::Color,
+        default: "color_1",
+    },
+    ParamSpec {
+        name: "background_color",
+        description: "color of background_char",
+        kind: ParamKind::Color,
+        default: "background-alt",
+    },
+];
+
+#[derive(Default)]
+pub struct Spectrum {
+    bar_char: char,
+    background_char: char,
+    bar_width: usize,
+    decay: f32,
+    freq_range: (f32, f32),
+    color: Color,
+    background_color: Color,
+    features: AudioFeatures,
+}
+
+impl Animator for Spectrum {
+    fn init(&mut self, _config: &Config, params: &AnimatorParams) {
+        self.bar_char = params.char("char");
+        self.background_char = params.char("background_char");
+        self.bar_width = params.int("bar_width") as usize;
+        self.decay = params.float("decay");
+        self.freq_range = (params.float("min_freq"), params.float("max_freq"));
+        self.color = params.color("color");
+        self.background_color = params.color("background_color");
+    }
+
+    fn update(&mut self, _dt: f32, features: &AudioFeatures) {
+        self.features = *features;
+    }
+
+    fn render(&mut self, surface: &mut dyn Surface) {
+        let grid_height = surface.height();
+        let bars = surface.width().div_ceil(self.bar_width);
+        surface.fill(self.background_char, self.background_color);
+
+        // convert spectrum bands to log-scaled frequency to magnitude map
+        let (low, high) = self.freq_range;
+        let freq_spectrum: Vec<(f32, f32)> = self
+            .features
+            .spectrum
+            .iter()
+            .zip(self.features.spectrum_freqs.iter())
+            .map(|(sv, freq)| (*freq, sv.smoothed_val))
+            .filter(|(freq, _mag)| *freq >= low && *freq <= high)
+            .map(|(freq, mag)| (freq.log2(), (15.0 * mag).log10()))
+            .collect();
+        if freq_spectrum.is_empty() {
+            return;
+        }
+
+        let max_freq = freq_spectrum[freq_spectrum.len() - 1].0;
+        let min_freq = freq_spectrum[0].0;
+        let col_width = (max_freq - min_freq) / (bars as f32);
+        let mut heights = vec![0.0; bars];
+        for (i, height) in heights.iter_mut().enumerate() {
+            let range_start = min_freq + col_width * (i as f32);
+            let range_end = range_start + col_width;
+            let mut hits = 0;
+            for (freq, magnitude) in freq_spectrum.iter() {
+                if range_start <= *freq && *freq < range_end {
+                    *height += *magnitude;
+                    hits += 1;
+                }
+            }
+            *height /= hits as f32;
+        }
+
+        let cutoff = 0.1;
+        let mut last_nonzero_l = 0.0;
+        let mut last_nonzero_r = 0.0;
+        for height in heights.iter_mut() {
+            if *height > cutoff {
+                last_nonzero_l = *height;
+            } else {
+                *height = last_nonzero_l;
+            }
+            last_nonzero_l *= self.decay;
+        }
+
+        for i in 0..bars {
+            let rev_i = bars - i - 1;
+            if heights[rev_i] > cutoff {
+                last_nonzero_r = heights[rev_i];
+            } else {
+                heights[rev_i] = last_nonzero_r;
+            }
+            last_nonzero_r *= self.decay;
+        }
+
+        for (i, height) in heights.iter().enumerate() {
+            let col_height = ((*height * (grid_height as f32)) as usize).min(grid_height);
+            let x = i * self.bar_width;
+            let y = grid_height - col_height;
+            surface.draw_box(self.bar_char, self.color, x, y, self.bar_width, col_height);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use crate::animators::params::{AnimatorParams, ParamKind, ParamSpec};
+use crate::animators::Animator;
+use crate::audio_processing::AudioFeatures;
+use crate::colors::Color;
+use crate::config::Config;
+use crate::surface::Surface;
+
+pub const PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "char",
+        description: "drawn where the pattern is brightest",
+        kind: ParamKind::Char,
+        default: "*",
+    },
+    ParamSpec {
+        name: "mid_char",
+        description: "drawn where the pattern is faint",
+        kind: ParamKind::Char,
+        default: "+",
+    },
+    ParamSpec {
+        name: "background_char",
+        description: "drawn where the pattern is off",
+        kind: ParamKind::Char,
+        default: ".",
+    },
+    ParamSpec {
+        name: "speed",
+        description: "how fast the pattern moves",
+        kind: ParamKind::Float {
+            min: 0.0,
+            max: 100.0,
+        },
+        default: "1.0",
+    },
+    ParamSpec {
+        name: "color",
+        description: "color of char",
+        kind: ParamKind::Color,
+        default: "color_3",
+    },
+    ParamSpec {
+        name: "mid_color",
+        description: "color of mid_char",
+        kind: ParamKind::Color,
+        default: "background-alt",
+    },
+    ParamSpec {
+        name: "background_color",
+        description: "color of background_char",
+        kind: ParamKind::Color,
+        default: "background-alt",
+    },
+];
+
+#[derive(Default)]
+pub struct Wiggly {
+    // (character, color) for the bright, faint and off parts of the pattern
+    cells: [(char, Color); 3],
+    speed: f32,
+    rms: f32,
+    zcr: f32,
+    // Seconds this animator has been running, drives the pattern's motion
+    time: f32,
+}
+
+impl Animator for Wiggly {
+    fn init(&mut self, _config: &Config, params: &AnimatorParams) {
+        self.cells = [
+            (params.char("char"), params.color("color")),
+            (params.char("mid_char"), params.color("mid_color")),
+            (
+                params.char("background_char"),
+                params.color("background_color"),
+            ),
+        ];
+        self.speed = params.float("speed");
+    }
+
+    fn update(&mut self, dt: f32, features: &AudioFeatures) {
+        self.rms = features.root_mean_squared.smoothed_val;
+        self.zcr = features.zero_crossing_rate.smoothed_val;
+        self.time += dt * self.speed;
+    }
+
+    fn render(&mut self, surface: &mut dyn Surface) {
+        let (rms, zcr) = (self.rms, self.zcr);
+        let center_x = surface.width() / 2;
+        let center_y = surface.height() / 2;
+        for i in 0..surface.width() {
+            for j in 0..surface.height() {
+                let dist_x = (i as f32) - (center_x as f32);
+                let dist_y = (j as f32) - (center_y as f32);
+
+                let mut sin_out = (0.05 * (zcr * 1.8 + 0.2) * dist_y * dist_x + self.time).sin();
+                sin_out = (sin_out + 1.0) / 2.0;
+                sin_out *= rms * (rms + 0.08) * 1.2;
+
+                let (c, col) = if sin_out > 0.5 {
+                    self.cells[0]
+                } else if sin_out > 0.01 {
+                    self.cells[1]
+                } else {
+                    self.cells[2]
+                };
+                surface.set_cell(c, col, i, j);
+            }
+        }
+    }
+}
@@ -1,44 +1,432 @@
-// Implements logic for converting buffers of different audio sample types to Vec<f32>
+// Implements logic for converting buffers of different audio sample types to f32.
+// Integers are scaled by their symmetric range 2^(bits - 1), so full scale maps to [-1, 1).
 
-pub trait AsF32Audio {
-    fn as_f32_samples(&self) -> Vec<f32>;
+use std::str::FromStr;
+
+// A single sample of a typed buffer, e.g. as delivered by cpal
+pub trait ToF32Sample: Copy {
+    fn to_f32_sample(self) -> f32;
+}
+
+impl ToF32Sample for i8 {
+    fn to_f32_sample(self) -> f32 {
+        (self as f32) / 128.0
+    }
+}
+
+impl ToF32Sample for i16 {
+    fn to_f32_sample(self) -> f32 {
+        (self as f32) / 32_768.0
+    }
 }
 
-impl AsF32Audio for [i8] {
-    fn as_f32_samples(&self) -> Vec<f32> {
-        self.iter()
-            .map(|i| (*i as f32) / (i8::MAX as f32))
-            .collect()
+impl ToF32Sample for i32 {
+    fn to_f32_sample(self) -> f32 {
+        ((self as f64) / 2_147_483_648.0) as f32
     }
 }
 
-impl AsF32Audio for [i16] {
-    fn as_f32_samples(&self) -> Vec<f32> {
-        self.iter()
-            .map(|i| (*i as f32) / (i16::MAX as f32))
-            .collect()
+impl ToF32Sample for i64 {
+    fn to_f32_sample(self) -> f32 {
+        ((self as f64) / 9_223_372_036_854_775_808.0) as f32
     }
 }
 
-impl AsF32Audio for [i32] {
-    fn as_f32_samples(&self) -> Vec<f32> {
-        self.iter()
-            .map(|i| (*i as f32) / (i32::MAX as f32))
-            .collect()
+// Unsigned samples are centered on half their range
+
+impl ToF32Sample for u8 {
+    fn to_f32_sample(self) -> f32 {
+        ((self as f32) - 128.0) / 128.0
     }
 }
 
-impl AsF32Audio for [f32] {
-    fn as_f32_samples(&self) -> Vec<f32> {
-        self.to_vec()
+impl ToF32Sample for u16 {
+    fn to_f32_sample(self) -> f32 {
+        ((self as f32) - 32_768.0) / 32_768.0
+    }
+}
+
+impl ToF32Sample for u32 {
+    fn to_f32_sample(self) -> f32 {
+        (((self as f64) - 2_147_483_648.0) / 2_147_483_648.0) as f32
+    }
+}
+
+impl ToF32Sample for u64 {
+    fn to_f32_sample(self) -> f32 {
+        let mid = 9_223_372_036_854_775_808.0;
+        (((self as f64) - mid) / mid) as f32
+    }
+}
+
+impl ToF32Sample for f32 {
+    fn to_f32_sample(self) -> f32 {
+        self
+    }
+}
+
+impl ToF32Sample for f64 {
+    fn to_f32_sample(self) -> f32 {
+        self as f32
+    }
+}
+
+// Byte layouts of raw sample streams, e.g. PCM read from pulse or received over the network.
+// S24 is packed, 3 bytes per sample.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SampleFormat {
+    U8,
+    S16LE,
+    S16BE,
+    S24LE,
+    S24BE,
+    S32LE,
+    S32BE,
+    F32LE,
+    F32BE,
+    F64LE,
+    F64BE,
+}
+
+impl FromStr for SampleFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "u8" => Ok(SampleFormat::U8),
+            "s16le" => Ok(SampleFormat::S16LE),
+            "s16be" | "l16" => Ok(SampleFormat::S16BE),
+            "s24le" => Ok(SampleFormat::S24LE),
+            "s24be" | "l24" => Ok(SampleFormat::S24BE),
+            "s32le" => Ok(SampleFormat::S32LE),
+            "s32be" => Ok(SampleFormat::S32BE),
+            "f32le" | "float32le" => Ok(SampleFormat::F32LE),
+            "f32be" | "float32be" => Ok(SampleFormat::F32BE),
+            "f64le" | "float64le" => Ok(SampleFormat::F64LE),
+            "f64be" | "float64be" => Ok(SampleFormat::F64BE),
+            _ => Err(format!("Unknown sample format '{s}'")),
+        }
+    }
+}
+
+impl SampleFormat {
+    pub fn bytes_per_sample(self) -> usize {
+        match self {
+            SampleFormat::U8 => 1,
+            SampleFormat::S16LE | SampleFormat::S16BE => 2,
+            SampleFormat::S24LE | SampleFormat::S24BE => 3,
+            SampleFormat::S32LE | SampleFormat::S32BE => 4,
+            SampleFormat::F32LE | SampleFormat::F32BE => 4,
+            SampleFormat::F64LE | SampleFormat::F64BE => 8,
+        }
+    }
+
+    // One sample, `b` holds exactly bytes_per_sample bytes
+    fn decode_sample(self, b: &[u8]) -> f32 {
+        match self {
+            SampleFormat::U8 => b[0].to_f32_sample(),
+            SampleFormat::S16LE => i16::from_le_bytes([b[0], b[1]]).to_f32_sample(),
+            SampleFormat::S16BE => i16::from_be_bytes([b[0], b[1]]).to_f32_sample(),
+            // Place the 24 bits at the top of an i32, the value is then scaled by 2^31
+            SampleFormat::S24LE => i32::from_le_bytes([0, b[0], b[1], b[2]]).to_f32_sample(),
+            SampleFormat::S24BE => i32::from_be_bytes([b[0], b[1], b[2], 0]).to_f32_sample(),
+            SampleFormat::S32LE => i32::from_le_bytes([b[0], b[1], b[2], b[3]]).to_f32_sample(),
+            SampleFormat::S32BE => i32::from_be_bytes([b[0], b[1], b[2], b[3]]).to_f32_sample(),
+            SampleFormat::F32LE => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            SampleFormat::F32BE => f32::from_be_bytes([b[0], b[1], b[2], b[3]]),
+            SampleFormat::F64LE => f64::from_le_bytes(b.try_into().unwrap_or_default()) as f32,
+            SampleFormat::F64BE => f64::from_be_bytes(b.try_into().unwrap_or_default()) as f32,
+        }
+    }
+
+    // Appends the samples in this format, clipping anything outside [-1, 1]
+    pub fn encode(self, samples: &[f32], bytes: &mut Vec<u8>) {
+        for sample in samples.iter().map(|sample| sample.clamp(-1.0, 1.0)) {
+            // Inverse of the symmetric scaling, +1.0 saturates to the largest value
+            let pcm = |range: f64| ((sample as f64) * range).round().clamp(-range, range - 1.0);
+            match self {
+                SampleFormat::U8 => bytes.push((pcm(128.0) + 128.0) as u8),
+                SampleFormat::S16LE => bytes.extend((pcm(32_768.0) as i16).to_le_bytes()),
+                SampleFormat::S16BE => bytes.extend((pcm(32_768.0) as i16).to_be_bytes()),
+                SampleFormat::S24LE => bytes.extend(&(pcm(8_388_608.0) as i32).to_le_bytes()[..3]),
+                SampleFormat::S24BE => bytes.extend(&(pcm(8_388_608.0) as i32).to_be_bytes()[1..]),
+                SampleFormat::S32LE => bytes.extend((pcm(2_147_483_648.0) as i32).to_le_bytes()),
+                SampleFormat::S32BE => bytes.extend((pcm(2_147_483_648.0) as i32).to_be_bytes()),
+                SampleFormat::F32LE => bytes.extend(sample.to_le_bytes()),
+                SampleFormat::F32BE => bytes.extend(sample.to_be_bytes()),
+                SampleFormat::F64LE => bytes.extend((sample as f64).to_le_bytes()),
+                SampleFormat::F64BE => bytes.extend((sample as f64).to_be_bytes()),
+            }
+        }
+    }
+
+    // Appends whole samples to `samples`, a trailing partial sample is ignored
+    pub fn decode_into(self, bytes: &[u8], samples: &mut Vec<f32>) {
+        samples.extend(
+            bytes
+                .chunks_exact(self.bytes_per_sample())
+                .map(|b| self.decode_sample(b)),
+        );
+    }
+
+    pub fn decode(self, bytes: &[u8]) -> Vec<f32> {
+        let mut samples = Vec::with_capacity(bytes.len() / self.bytes_per_sample());
+        self.decode_into(bytes, &mut samples);
+        samples
     }
 }
 
-impl AsF32Audio for [u8] {
-    // Interprets each u8 as a byte, with each group of 4 comprising one f32
-    fn as_f32_samples(&self) -> Vec<f32> {
-        self.chunks(4)
-            .map(|bytes: &[u8]| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
-            .collect()
+// Decodes a byte stream that arrives in arbitrary pieces, e.g. reads from a socket or pipe.
+// Only whole frames are emitted, the bytes of a partial frame are kept for the next read.
+pub struct SampleDecoder {
+    format: SampleFormat,
+    frame_size: usize,
+    carry: Vec<u8>,
+}
+
+impl SampleDecoder {
+    pub fn new(format: SampleFormat, channels: usize) -> SampleDecoder {
+        let frame_size = format.bytes_per_sample() * channels.max(1);
+        SampleDecoder {
+            format,
+            frame_size,
+            carry: Vec::with_capacity(frame_size),
+        }
+    }
+
+    // Replaces the contents of `samples` with every frame completed by `bytes`.
+    // Doesn't allocate once `samples` has grown to the read size.
+    pub fn decode_into(&mut self, mut bytes: &[u8], samples: &mut Vec<f32>) {
+        samples.clear();
+
+        // finish the frame left over from the previous read
+        if !self.carry.is_empty() {
+            let missing = (self.frame_size - self.carry.len()).min(bytes.len());
+            self.carry.extend_from_slice(&bytes[..missing]);
+            bytes = &bytes[missing..];
+            if self.carry.len() < self.frame_size {
+                return;
+            }
+            self.format.decode_into(&self.carry, samples);
+            self.carry.clear();
+        }
+
+        let whole = bytes.len() - bytes.len() % self.frame_size;
+        self.format.decode_into(&bytes[..whole], samples);
+        self.carry.extend_from_slice(&bytes[whole..]);
+    }
+
+    // Drops a partial frame, e.g. after the stream was interrupted
+    pub fn reset(&mut self) {
+        self.carry.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMATS: [SampleFormat; 11] = [
+        SampleFormat::U8,
+        SampleFormat::S16LE,
+        SampleFormat::S16BE,
+        SampleFormat::S24LE,
+        SampleFormat::S24BE,
+        SampleFormat::S32LE,
+        SampleFormat::S32BE,
+        SampleFormat::F32LE,
+        SampleFormat::F32BE,
+        SampleFormat::F64LE,
+        SampleFormat::F64BE,
+    ];
+
+    // Largest error of a round trip, half a step of the integer formats
+    fn step(format: SampleFormat) -> f32 {
+        match format {
+            SampleFormat::U8 => 1.0 / 128.0,
+            SampleFormat::S16LE | SampleFormat::S16BE => 1.0 / 32_768.0,
+            SampleFormat::S24LE | SampleFormat::S24BE => 1.0 / 8_388_608.0,
+            // beyond f32's precision anyway
+            _ => 1e-7,
+        }
+    }
+
+    // Random cases per format in the property tests
+    const CASES: usize = 200;
+
+    // xorshift64* with a fixed seed, so a failing case comes back on every run
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n.max(1) as u64) as usize
+        }
+
+        // Mostly within full scale, some beyond it
+        fn sample(&mut self) -> f32 {
+            (self.next() >> 40) as f32 / (1u64 << 24) as f32 * 2.4 - 1.2
+        }
+    }
+
+    fn encode(format: SampleFormat, samples: &[f32]) -> Vec<u8> {
+        let mut bytes = vec![];
+        format.encode(samples, &mut bytes);
+        bytes
+    }
+
+    #[test]
+    fn every_format_round_trips() {
+        let samples = [0.0, 0.5, -0.5, 0.25, -0.75, 0.123, -0.987, 0.99];
+        for format in FORMATS {
+            let bytes = encode(format, &samples);
+            assert_eq!(bytes.len(), samples.len() * format.bytes_per_sample());
+            let decoded = format.decode(&bytes);
+            assert_eq!(decoded.len(), samples.len());
+            for (sample, decoded) in samples.iter().zip(decoded.iter()) {
+                assert!(
+                    (sample - decoded).abs() <= step(format) / 2.0 + 1e-7,
+                    "{format:?}: {sample} decoded as {decoded}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn random_samples_round_trip() {
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        for format in FORMATS {
+            for _ in 0..CASES {
+                let samples: Vec<f32> = (0..rng.below(64)).map(|_| rng.sample()).collect();
+                let bytes = encode(format, &samples);
+                assert_eq!(bytes.len(), samples.len() * format.bytes_per_sample());
+                let decoded = format.decode(&bytes);
+                for (sample, decoded) in samples.iter().zip(decoded.iter()) {
+                    // within a step, the clamp takes the top one off +1.0
+                    let expected = sample.clamp(-1.0, 1.0);
+                    assert!(
+                        (expected - decoded).abs() <= step(format),
+                        "{format:?}: {sample} decoded as {decoded}"
+                    );
+                }
+                // decoded values sit on the format's steps, so they come back unchanged
+                assert_eq!(
+                    format.decode(&encode(format, &decoded)),
+                    decoded,
+                    "{format:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn decoder_carries_across_random_splits() {
+        let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+        for format in FORMATS {
+            for _ in 0..CASES {
+                let channels = 1 + rng.below(8);
+                let frame_size = channels * format.bytes_per_sample();
+                let samples: Vec<f32> = (0..channels * rng.below(32))
+                    .map(|_| rng.sample())
+                    .collect();
+                let mut bytes = encode(format, &samples);
+                let whole = bytes.len();
+                // a partial frame at the end is held back, never emitted
+                bytes.extend((0..rng.below(frame_size)).map(|_| rng.next() as u8));
+
+                let mut decoder = SampleDecoder::new(format, channels);
+                let (mut decoded, mut read) = (vec![], vec![]);
+                let mut rest = &bytes[..];
+                while !rest.is_empty() {
+                    let size = 1 + rng.below(rest.len().min(3 * frame_size));
+                    decoder.decode_into(&rest[..size], &mut read);
+                    assert_eq!(read.len() % channels, 0, "{format:?}: half a frame emitted");
+                    decoded.extend_from_slice(&read);
+                    rest = &rest[size..];
+                }
+                assert_eq!(decoded, format.decode(&bytes[..whole]), "{format:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn endianness_is_byte_order() {
+        let pairs = [
+            (SampleFormat::S16LE, SampleFormat::S16BE),
+            (SampleFormat::S24LE, SampleFormat::S24BE),
+            (SampleFormat::S32LE, SampleFormat::S32BE),
+            (SampleFormat::F32LE, SampleFormat::F32BE),
+            (SampleFormat::F64LE, SampleFormat::F64BE),
+        ];
+        for (le, be) in pairs {
+            let mut bytes = encode(le, &[0.3]);
+            bytes.reverse();
+            assert_eq!(bytes, encode(be, &[0.3]), "{le:?} and {be:?}");
+        }
+        assert_eq!(encode(SampleFormat::S16LE, &[0.5]), [0x00, 0x40]);
+        assert_eq!(encode(SampleFormat::S24BE, &[-0.5]), [0xC0, 0x00, 0x00]);
+        assert_eq!(encode(SampleFormat::U8, &[0.0, -1.0]), [128, 0]);
+    }
+
+    #[test]
+    fn full_scale_clamps_symmetrically() {
+        for format in FORMATS {
+            let decoded = format.decode(&encode(format, &[1.0, -1.0, 4.0, -4.0]));
+            // the integer formats have one more step below zero than above
+            assert!(
+                (decoded[0] - 1.0).abs() <= step(format),
+                "{format:?}: {decoded:?}"
+            );
+            assert_eq!(decoded[1], -1.0, "{format:?}");
+            assert_eq!(decoded[2], decoded[0], "{format:?}");
+            assert_eq!(decoded[3], decoded[1], "{format:?}");
+        }
+        assert_eq!(encode(SampleFormat::S16LE, &[2.0]), 32_767i16.to_le_bytes());
+        assert_eq!(
+            encode(SampleFormat::S16LE, &[-2.0]),
+            (-32_768i16).to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn decoder_joins_frames_split_across_reads() {
+        let samples: Vec<f32> = (0..12).map(|i| i as f32 / 16.0 - 0.4).collect();
+        for format in FORMATS {
+            let bytes = encode(format, &samples);
+            let expected = format.decode(&bytes);
+            // stereo, with reads cut in the middle of samples and of frames
+            for read_size in [1, 3, 5, 7, 16] {
+                let mut decoder = SampleDecoder::new(format, 2);
+                let (mut decoded, mut read) = (vec![], vec![]);
+                for chunk in bytes.chunks(read_size) {
+                    decoder.decode_into(chunk, &mut read);
+                    assert_eq!(read.len() % 2, 0, "{format:?}: half a frame emitted");
+                    decoded.extend_from_slice(&read);
+                }
+                assert_eq!(
+                    decoded, expected,
+                    "{format:?} read {read_size} bytes at a time"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn decoder_reset_drops_partial_frame() {
+        let format = SampleFormat::S16LE;
+        let bytes = encode(format, &[0.5, -0.5]);
+        let mut decoder = SampleDecoder::new(format, 1);
+        let mut samples = vec![];
+        decoder.decode_into(&bytes[..3], &mut samples);
+        assert_eq!(samples, [0.5]);
+        decoder.reset();
+        decoder.decode_into(&bytes[..2], &mut samples);
+        assert_eq!(samples, [0.5]);
     }
 }
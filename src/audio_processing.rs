@@ -1,10 +1,25 @@
 use rustfft::{num_complex::Complex, FftPlanner};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+
+use crate::feature_log::FeatureRecorder;
+use crate::filters::{FilterChain, FilterSpec, FilterView};
+use crate::input::mix::MixBus;
+use crate::input::InputStatus;
+use crate::preprocess::{PreprocessSettings, Preprocessor};
+use crate::resample::Resampler;
+use crate::spectrum::{MultiResolutionSpectrum, SpectrumSource};
+use crate::tap::AudioTap;
 
 const BUFFER_SIZE: usize = 800;
+// Samples of the analysed signal kept in the features, one analysis buffer
+pub const WAVEFORM_SIZE: usize = BUFFER_SIZE;
 const FFT_SIZE: usize = 800;
+pub const SPECTRUM_SIZE: usize = FFT_SIZE / 2;
 const SMOOTHING_SIZE: usize = 12;
-const FS: usize = 48000;
+// Samples handed to the mix bus at once
+const MIX_BATCH: usize = 256;
+// Sample rate the analysis constants are derived from
+pub const FS: usize = 48000;
 const FFT_BIN_WIDTH: f32 = (FS as f32) / (FFT_SIZE as f32);
 
 pub fn bin_idx_to_center_freq(bin_idx: usize) -> f32 {
@@ -15,21 +30,170 @@ pub fn bin_idx_to_freq(bin_idx: usize) -> f32 {
     (bin_idx as f32) * FFT_BIN_WIDTH
 }
 
+// Analysis options chosen in config, shared by every input backend
+#[derive(Clone)]
+pub struct AnalysisSettings {
+    pub spectrum_source: SpectrumSource,
+    // Convert inputs to FS before analysis, otherwise every rate is analysed as if it were FS
+    pub resample: bool,
+    // RMS level (dBFS) under which the input counts as silent
+    pub silence_threshold_db: f32,
+    // How long the input must stay under the threshold before it is reported as silent
+    pub silence_hold_s: f32,
+    pub preprocess: PreprocessSettings,
+    // Biquad stages applied before analysis
+    pub filters: Vec<FilterSpec>,
+    // Extra filtered views, each analysed on its own
+    pub views: Vec<FilterView>,
+}
+
+impl Default for AnalysisSettings {
+    fn default() -> Self {
+        AnalysisSettings {
+            spectrum_source: SpectrumSource::default(),
+            resample: true,
+            silence_threshold_db: -60.0,
+            silence_hold_s: 3.0,
+            preprocess: PreprocessSettings::default(),
+            filters: vec![],
+            views: vec![],
+        }
+    }
+}
+
 pub struct AudioProcessBuffer {
     buffer: [f32; BUFFER_SIZE],
     head: usize,
     fft: Arc<dyn rustfft::Fft<f32>>,
+    preprocessor: Preprocessor,
+    filters: FilterChain,
+    views: Vec<(String, AudioProcessBuffer)>,
+    multi_resolution: Option<MultiResolutionSpectrum>,
+    settings: AnalysisSettings,
+    recorder: Option<FeatureRecorder>,
+    tap: Option<AudioTap>,
+    // Rate the backend delivers samples at
+    input_rate: u32,
+    resampler: Option<Resampler>,
+    mix: Option<MixSend>,
     pub features: AudioFeatures,
+    pub status: InputStatus,
 }
 
 impl AudioProcessBuffer {
-    pub fn new() -> AudioProcessBuffer {
+    pub fn new(settings: AnalysisSettings) -> AudioProcessBuffer {
         let mut planner = FftPlanner::new();
+        let multi_resolution = match settings.spectrum_source {
+            SpectrumSource::Fft => None,
+            SpectrumSource::MultiResolution => Some(MultiResolutionSpectrum::new(FS as f32)),
+        };
+        // Views share the preprocessing done here, then apply their own filters
+        let views = settings
+            .views
+            .iter()
+            .map(|view| {
+                let view_settings = AnalysisSettings {
+                    preprocess: PreprocessSettings::default(),
+                    filters: view.filters.clone(),
+                    views: vec![],
+                    ..settings.clone()
+                };
+                (view.name.clone(), AudioProcessBuffer::new(view_settings))
+            })
+            .collect();
+        let mut features = AudioFeatures::new();
+        match &multi_resolution {
+            Some(spectrum) => features.spectrum_freqs = *spectrum.band_freqs(),
+            None => {
+                for (i, freq) in features.spectrum_freqs.iter_mut().enumerate() {
+                    *freq = bin_idx_to_freq(i);
+                }
+            }
+        }
         AudioProcessBuffer {
             buffer: [0.0; BUFFER_SIZE],
             head: 0,
             fft: planner.plan_fft_forward(FFT_SIZE),
-            features: AudioFeatures::new(),
+            preprocessor: Preprocessor::new(settings.preprocess, FS as f32),
+            filters: FilterChain::new(&settings.filters, FS as f32),
+            views,
+            multi_resolution,
+            settings,
+            recorder: None,
+            tap: None,
+            input_rate: FS as u32,
+            resampler: None,
+            mix: None,
+            features,
+            status: InputStatus::Connecting,
+        }
+    }
+
+    // Features of a filtered view, by name
+    pub fn view_features(self: &AudioProcessBuffer, name: &str) -> Option<&AudioFeatures> {
+        self.views
+            .iter()
+            .find(|(view_name, _view)| view_name == name)
+            .map(|(_view_name, view)| &view.features)
+    }
+
+    // Sends every published snapshot, including the views, to the recorder
+    pub fn record_features(self: &mut AudioProcessBuffer, recorder: FeatureRecorder) {
+        self.recorder = Some(recorder);
+    }
+
+    // Tees every sample the backend pushes, before any processing
+    pub fn tap_audio(self: &mut AudioProcessBuffer, tap: AudioTap) {
+        self.tap = Some(tap);
+    }
+
+    // Saves the tap's rolling window, returns the file name when there is one
+    pub fn dump_tap(self: &AudioProcessBuffer) -> Option<String> {
+        self.tap.as_ref()?.dump(self.input_rate)
+    }
+
+    // A failed write of the tap or the recorder, for the UI to show
+    pub fn write_error(self: &AudioProcessBuffer) -> Option<String> {
+        let tap_error = self.tap.as_ref().and_then(AudioTap::take_error);
+        tap_error.or_else(|| self.recorder.as_ref().and_then(FeatureRecorder::take_error))
+    }
+
+    // Forwards the resampled input to a mix of several sources, as input number `source`
+    pub fn send_to_mix(self: &mut AudioProcessBuffer, bus: Arc<Mutex<MixBus>>, source: usize) {
+        self.mix = Some(MixSend {
+            bus,
+            source,
+            pending: Vec::with_capacity(MIX_BATCH),
+        });
+    }
+
+    // Called by backends once the stream's rate is known
+    pub fn set_input_rate(self: &mut AudioProcessBuffer, sample_rate: u32) {
+        // Reconnects report the same rate again, keep the resampler's state
+        if sample_rate == self.input_rate {
+            return;
+        }
+        self.input_rate = sample_rate;
+        self.resampler = match self.settings.resample && sample_rate != FS as u32 {
+            true => Some(Resampler::new(sample_rate, FS as u32)),
+            false => None,
+        };
+    }
+
+    // Replaces the features with ones analysed elsewhere, e.g. a recording.
+    // Views are matched by name, views missing from `views` keep their features.
+    pub fn publish(
+        self: &mut AudioProcessBuffer,
+        features: &AudioFeatures,
+        views: &[(String, AudioFeatures)],
+    ) {
+        self.features = *features;
+        for (name, view) in self.views.iter_mut() {
+            if let Some((_name, view_features)) =
+                views.iter().find(|(view_name, _)| view_name == name)
+            {
+                view.features = *view_features;
+            }
         }
     }
 
@@ -37,16 +201,58 @@ impl AudioProcessBuffer {
         self.buffer.len() - self.head
     }
 
+    // Takes a sample at the input rate
     pub fn push(self: &mut AudioProcessBuffer, value: f32) {
-        if self.remaining_cap() == 0 {
+        if let Some(tap) = self.tap.as_mut() {
+            tap.push(value, self.input_rate);
+        }
+        match self.resampler.take() {
+            Some(mut resampler) => {
+                resampler.process(value, |resampled| self.push_resampled(resampled));
+                self.resampler = Some(resampler);
+            }
+            None => self.push_resampled(value),
+        }
+    }
+
+    fn push_resampled(self: &mut AudioProcessBuffer, value: f32) {
+        if let Some(mix) = self.mix.as_mut() {
+            mix.push(value);
+        }
+        self.push_analysis(value);
+    }
+
+    // Takes a sample at the analysis rate
+    fn push_analysis(self: &mut AudioProcessBuffer, value: f32) {
+        let published = self.remaining_cap() == 0;
+        if published {
             self.process_full_buffer();
         }
+        let value = self.preprocessor.process(value);
+        for (_name, view) in self.views.iter_mut() {
+            view.push_analysis(value);
+        }
+        let value = self.filters.process(value);
         self.buffer[self.head] = value;
         self.head += 1;
+        if let Some(spectrum) = self.multi_resolution.as_mut() {
+            spectrum.push(value);
+        }
+
+        // Views publish during the same push, so the snapshot covers all of them
+        if let (true, Some(recorder)) = (published, &self.recorder) {
+            let views = self
+                .views
+                .iter()
+                .map(|(name, view)| (name.clone(), view.features))
+                .collect();
+            recorder.record(&self.features, views);
+        }
     }
 
     pub fn process_full_buffer(self: &mut AudioProcessBuffer) {
         self.head = 0;
+        self.features.waveform = self.buffer;
 
         // Time domain features
         self.compute_root_mean_squared();
@@ -55,12 +261,24 @@ impl AudioProcessBuffer {
         // Frequency domain features
         let fft = self.compute_fft();
         self.compute_eq(fft);
+        self.compute_multi_resolution_spectrum();
     }
 
     fn compute_root_mean_squared(&mut self) {
         let sum_of_squares: f32 = self.buffer.iter().map(|x| x * x).sum();
         let rms: f32 = (sum_of_squares / (self.buffer.len() as f32)).sqrt();
         self.features.root_mean_squared.write(rms);
+        self.detect_silence(rms);
+    }
+
+    fn detect_silence(&mut self, rms: f32) {
+        let level_db = 20.0 * rms.max(f32::MIN_POSITIVE).log10();
+        if level_db < self.settings.silence_threshold_db {
+            self.features.silence_duration += (BUFFER_SIZE as f32) / (FS as f32);
+        } else {
+            self.features.silence_duration = 0.0;
+        }
+        self.features.silent = self.features.silence_duration >= self.settings.silence_hold_s;
     }
 
     fn compute_zero_crossing_rate(&mut self) {
@@ -135,7 +353,7 @@ impl AudioProcessBuffer {
         // Cut off mirrored frequencies
         fft_buffer = fft_buffer[0..((fft_buffer.len()) / 2)].to_vec();
 
-        // as_f32_samples to magnitudes
+        // Complex bins to magnitudes
         let magnitudes: Vec<f32> = fft_buffer.iter().map(|complex| complex.norm()).collect();
         // Normalize
         let mut max_mag = 1.0;
@@ -150,14 +368,51 @@ impl AudioProcessBuffer {
         for (i, mag) in magnitudes.iter().enumerate() {
             self.features.fft_bins[i].write(*mag);
         }
+        if self.multi_resolution.is_none() {
+            for (i, mag) in magnitudes.iter().enumerate() {
+                self.features.spectrum[i].write(*mag);
+            }
+        }
 
         magnitudes
     }
+
+    fn compute_multi_resolution_spectrum(&mut self) {
+        let Some(spectrum) = self.multi_resolution.as_mut() else {
+            return;
+        };
+        let magnitudes = spectrum.compute(FFT_SIZE);
+
+        // Normalize the same way as the plain FFT
+        let max_mag = magnitudes.iter().fold(1.0, |max: f32, mag| max.max(*mag));
+        for (i, mag) in magnitudes.iter().enumerate() {
+            self.features.spectrum[i].write(mag / max_mag);
+        }
+    }
+}
+
+// Batches samples for the mix bus, so its lock is taken once per batch
+struct MixSend {
+    bus: Arc<Mutex<MixBus>>,
+    source: usize,
+    pending: Vec<f32>,
+}
+
+impl MixSend {
+    fn push(&mut self, value: f32) {
+        self.pending.push(value);
+        if self.pending.len() >= MIX_BATCH {
+            if let Ok(mut bus) = self.bus.lock() {
+                bus.push(self.source, &self.pending);
+            }
+            self.pending.clear();
+        }
+    }
 }
 
 impl Default for AudioProcessBuffer {
     fn default() -> Self {
-        Self::new()
+        Self::new(AnalysisSettings::default())
     }
 }
 
@@ -166,9 +421,24 @@ pub struct AudioFeatures {
     pub root_mean_squared: SmoothedValue,
     pub zero_crossing_rate: SmoothedValue,
     pub fft_bins: [SmoothedValue; FFT_SIZE / 2],
+    // Spectrum from the configured source, with the center frequency of each band
+    pub spectrum: [SmoothedValue; SPECTRUM_SIZE],
+    pub spectrum_freqs: [f32; SPECTRUM_SIZE],
+    // The last analysed samples after preprocessing and filters, oldest first
+    pub waveform: [f32; WAVEFORM_SIZE],
     pub lo: SmoothedValue,
     pub mi: SmoothedValue,
     pub hi: SmoothedValue,
+    // Seconds the input has been under the silence threshold
+    pub silence_duration: f32,
+    // Set once silence has lasted longer than the configured hold time
+    pub silent: bool,
+}
+
+impl Default for AudioFeatures {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl AudioFeatures {
@@ -177,9 +447,14 @@ impl AudioFeatures {
             root_mean_squared: SmoothedValue::new(0.0, true, false),
             zero_crossing_rate: SmoothedValue::new(0.0, false, false),
             fft_bins: [SmoothedValue::new(0.0, false, false); FFT_SIZE / 2],
+            spectrum: [SmoothedValue::new(0.0, false, false); SPECTRUM_SIZE],
+            spectrum_freqs: [0.0; SPECTRUM_SIZE],
+            waveform: [0.0; WAVEFORM_SIZE],
             lo: SmoothedValue::new(0.0, false, false),
             mi: SmoothedValue::new(0.0, false, false),
             hi: SmoothedValue::new(0.0, false, false),
+            silence_duration: 0.0,
+            silent: false,
         }
     }
 }
@@ -5,6 +5,12 @@ pub const BLOCK_CHAR: char = '\u{2588}';
 
 pub type Color = (u8, u8, u8);
 
+// The color `amount` of the way from `a` to `b`
+pub fn lerp(a: Color, b: Color, amount: f32) -> Color {
+    let channel = |a: u8, b: u8| ((a as f32) + ((b as f32) - (a as f32)) * amount) as u8;
+    (channel(a.0, b.0), channel(a.1, b.1), channel(a.2, b.2))
+}
+
 pub trait FromHex: Sized {
     fn from_hex_string(hex_str: String) -> Result<Self, String>;
 }
@@ -19,7 +25,8 @@ impl FromHex for Color {
         if hex_str.len() != 6 {
             return Err("Invalid Hex String: Invalid length".to_string());
         }
-        let hex_value: Vec<u8> = decode(hex_str).expect("Invalid Hex");
+        let hex_value: Vec<u8> =
+            decode(hex_str).map_err(|_| "Invalid Hex String: Not a hex number".to_string())?;
         Ok((hex_value[0], hex_value[1], hex_value[2]))
     }
 }
@@ -49,3 +56,27 @@ impl PartialEq for ColoredChar {
         self.c == other.c && self.color == other.color
     }
 }
+
+// The configured colors, copied into animators when they are initialized
+#[derive(Copy, Clone, Default)]
+pub struct Palette {
+    pub color_1: Color,
+    pub color_2: Color,
+    pub color_3: Color,
+    pub bg: Color,
+    pub bg_alt: Color,
+}
+
+impl Palette {
+    // A color by its key in [colors]
+    pub fn named(&self, name: &str) -> Option<Color> {
+        match name {
+            "color_1" => Some(self.color_1),
+            "color_2" => Some(self.color_2),
+            "color_3" => Some(self.color_3),
+            "background" => Some(self.bg),
+            "background-alt" => Some(self.bg_alt),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,171 @@
+// Stacks several animators on one surface, e.g. bars over a moving background.
+// Every layer is drawn into its own surface that starts out transparent, cells the
+// animator leaves empty (or draws as NO_CELL) show the layers below.
+// Layers with an [animation-sources] or [animation-filters] entry of their own read
+// that, e.g. a mic over the mix, the others get the features of the scene.
+
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use crate::animators::params::AnimatorParams;
+use crate::animators::{Animation, Animator};
+use crate::audio_processing::{AudioFeatures, AudioProcessBuffer};
+use crate::colors::Color;
+use crate::config::Config;
+use crate::input::Sources;
+use crate::surface::{Surface, NO_CELL};
+use crate::terminal_grid::TerminalGrid;
+
+// How a layer's colors combine with what is below it
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum BlendMode {
+    // The layer's cells replace the ones below
+    #[default]
+    Normal,
+    Add,
+    Multiply,
+    Screen,
+    // The brighter of each color channel
+    Lighten,
+}
+
+impl FromStr for BlendMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(BlendMode::Normal),
+            "add" => Ok(BlendMode::Add),
+            "multiply" => Ok(BlendMode::Multiply),
+            "screen" => Ok(BlendMode::Screen),
+            "lighten" => Ok(BlendMode::Lighten),
+            _ => Err(format!(
+                "Unknown blend mode '{s}' (expected 'normal', 'add', 'multiply', 'screen' or 'lighten')"
+            )),
+        }
+    }
+}
+
+impl BlendMode {
+    fn blend(self, below: Color, above: Color) -> Color {
+        let channel = |below: u8, above: u8| -> u8 {
+            let (b, a) = (below as u32, above as u32);
+            match self {
+                BlendMode::Normal => above,
+                BlendMode::Add => (b + a).min(255) as u8,
+                BlendMode::Multiply => (b * a / 255) as u8,
+                BlendMode::Screen => (255 - (255 - b) * (255 - a) / 255) as u8,
+                BlendMode::Lighten => below.max(above),
+            }
+        };
+        (
+            channel(below.0, above.0),
+            channel(below.1, above.1),
+            channel(below.2, above.2),
+        )
+    }
+}
+
+struct Layer {
+    animation: Animation,
+    blend: BlendMode,
+    surface: TerminalGrid,
+    // The layer's own source, once attached
+    buffer: Option<Arc<Mutex<AudioProcessBuffer>>>,
+    // Last features read from `buffer`, kept while the backend holds the lock
+    features: AudioFeatures,
+}
+
+pub struct Compositor {
+    // Bottom layer first
+    layers: Vec<Layer>,
+}
+
+impl Compositor {
+    // `layers` are ordered bottom to top, the animators already initialized
+    pub fn new(config: &Config, layers: Vec<(Animation, BlendMode)>) -> Compositor {
+        Compositor {
+            layers: layers
+                .into_iter()
+                .map(|(animation, blend)| Layer {
+                    animation,
+                    blend,
+                    surface: TerminalGrid::with_size(config.bg_color, 0, 0),
+                    buffer: None,
+                    features: AudioFeatures::default(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Animator for Compositor {
+    // The layers read their own [animation.<name>] sections when they are created
+    fn init(&mut self, _config: &Config, _params: &AnimatorParams) {}
+
+    fn update(&mut self, dt: f32, features: &AudioFeatures) {
+        for layer in self.layers.iter_mut() {
+            let Some(buffer) = &layer.buffer else {
+                layer.animation.animator.update(dt, features);
+                continue;
+            };
+            if let Ok(buffer) = buffer.try_lock() {
+                layer.features = match layer.animation.view.as_deref() {
+                    Some(view) => *buffer.view_features(view).unwrap_or(&buffer.features),
+                    None => buffer.features,
+                };
+            }
+            layer.animation.animator.update(dt, &layer.features);
+        }
+    }
+
+    fn render(&mut self, surface: &mut dyn Surface) {
+        surface.clear();
+        let bg = surface.bg_color();
+        for layer in self.layers.iter_mut() {
+            layer.surface.fill(NO_CELL, bg);
+            layer.animation.animator.render(&mut layer.surface);
+
+            for x in 0..surface.width().min(layer.surface.width) {
+                for y in 0..surface.height().min(layer.surface.height) {
+                    let above = layer.surface.get_cell(x, y);
+                    if above.c == NO_CELL {
+                        continue;
+                    }
+                    let below = surface.get_cell(x, y);
+                    let color = layer.blend.blend(below.color, above.color);
+                    surface.set_cell(above.c, color, x, y);
+                }
+            }
+        }
+    }
+
+    fn on_resize(&mut self, width: usize, height: usize) {
+        for layer in self.layers.iter_mut() {
+            layer.surface.resize(width, height);
+            layer.animation.animator.on_resize(width, height);
+        }
+    }
+
+    fn on_enter(&mut self) {
+        for layer in self.layers.iter_mut() {
+            layer.animation.animator.on_enter();
+        }
+    }
+
+    fn on_exit(&mut self) {
+        for layer in self.layers.iter_mut() {
+            layer.animation.animator.on_exit();
+        }
+    }
+
+    fn attach_sources(&mut self, sources: &Sources) {
+        for layer in self.layers.iter_mut() {
+            let animation = &layer.animation;
+            if animation.source.is_some() || animation.view.is_some() {
+                layer.buffer = Some(sources.buffer(animation.source.as_deref()).clone());
+            }
+            layer.animation.animator.attach_sources(sources);
+        }
+    }
+}
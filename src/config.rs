@@ -1,13 +1,67 @@
 use clap::Parser;
 use configparser::ini::Ini;
+use std::collections::HashMap;
 
-use crate::colors::{Color, FromHex};
+use crate::animators::params::AnimatorParams;
+use crate::animators::{find_animator, REGISTRY};
+use crate::audio_formats::SampleFormat;
+use crate::audio_processing::AnalysisSettings;
+use crate::colors::{Color, FromHex, Palette};
+use crate::compositor::BlendMode;
+use crate::filters::{FilterSpec, FilterView};
+use crate::input::network::{NetworkSettings, Protocol};
+use crate::input::{Backend, CaptureMode, InputSettings};
+use crate::layout::{LayoutSettings, RegionSettings, Split};
+use crate::preprocess::PreprocessSettings;
+use crate::scheduler::{Order, ScheduleSettings, SwitchOn};
+use crate::spectrum::SpectrumSource;
+use crate::tap::{TapMode, TapSettings};
+use crate::transitions::Transition;
 
 #[derive(Parser)]
-#[command(version, about = "Ascii Audio Visualizer", long_about = None)]
-struct CommandLineArgs {
+#[command(
+    version,
+    about = "Ascii Audio Visualizer",
+    long_about = None,
+    after_help = "[capture] in the config saves the input to a WAV. It holds the mono downmix the \
+                  analysis receives, as 16 bit PCM at the input's rate, not the channels or sample \
+                  format the device delivered."
+)]
+pub struct CommandLineArgs {
     #[arg(short, long, value_name = "config_path")]
-    config_path: Option<String>,
+    pub config_path: Option<String>,
+
+    /// Source or sink to capture, overrides 'device' in [input]
+    #[arg(short, long, value_name = "device")]
+    pub device: Option<String>,
+
+    /// List the audio devices that can be captured, then exit
+    #[arg(long)]
+    pub list_devices: bool,
+
+    /// List the available animations and their settings, then exit
+    #[arg(long)]
+    pub list_animations: bool,
+
+    /// Write every analysed feature snapshot to a file, for --replay-features
+    #[arg(long, value_name = "file")]
+    pub record_features: Option<String>,
+
+    /// Drive the animations from a recorded feature file instead of an audio backend
+    #[arg(long, value_name = "file", conflicts_with = "record_features")]
+    pub replay_features: Option<String>,
+}
+
+// Source name of [input], and of the sum of all sources when there are several
+pub const MAIN_SOURCE: &str = "main";
+pub const MIX_SOURCE: &str = "mix";
+
+// What to show once the input has been silent for a while
+pub enum IdleScreen {
+    // Keep the last frame on screen, dimmed
+    Dim,
+    // Switch to the named animation
+    Animation(String),
 }
 
 pub struct Config {
@@ -20,15 +74,60 @@ pub struct Config {
 
     // Animation Settings
     pub animation_length: i64,
+    // How the rotation moves from one animation to the next, and over how many seconds
+    pub transition: Transition,
+    pub transition_length: f32,
+    // When the rotation moves on and what comes next
+    pub schedule: ScheduleSettings,
 
     // Animations
     pub animations: Vec<String>,
+    // Filtered view each animation reads, by animation name
+    pub animation_views: HashMap<String, String>,
+    // Source each animation reads, by animation name
+    pub animation_sources: HashMap<String, String>,
+    // Seconds each animation plays, by animation name, duration_s for the others
+    pub animation_durations: HashMap<String, f32>,
+    // How likely random order picks each animation, by animation name, 1 for the others
+    pub animation_weights: HashMap<String, f32>,
+    // Scenes that stack several animations, bottom layer first, by scene name
+    pub layers: HashMap<String, Vec<(String, BlendMode)>>,
+    // Settings from the [animation.<name>] sections, for every registered animator
+    pub animation_params: HashMap<String, AnimatorParams>,
+    // Regions of the screen that each run the rotation or one animation
+    pub layout: LayoutSettings,
+
+    // Audio Settings
+    pub input: InputSettings,
+    // Extra inputs from [input.<name>] sections, captured alongside [input]
+    pub sources: Vec<(String, InputSettings)>,
+    pub analysis: AnalysisSettings,
+
+    // Idle Settings
+    pub idle_screen: IdleScreen,
+    pub idle_fps: f32,
+
+    // Audio capture to WAV
+    pub tap: TapSettings,
 }
 
 impl Config {
-    pub fn load_config() -> Config {
-        let args = CommandLineArgs::parse();
-        Config::new(args.config_path)
+    pub fn palette(&self) -> Palette {
+        Palette {
+            color_1: self.color_1,
+            color_2: self.color_2,
+            color_3: self.color_3,
+            bg: self.bg_color,
+            bg_alt: self.bg_alt_color,
+        }
+    }
+
+    pub fn load_config(args: &CommandLineArgs) -> Config {
+        let mut config = Config::new(args.config_path.clone());
+        if args.device.is_some() {
+            config.input.device = args.device.clone();
+        }
+        config
     }
 
     pub fn new(config_path: Option<String>) -> Config {
@@ -67,26 +166,550 @@ impl Config {
             .getint("animation-settings", "duration_s")
             .expect("Error: 'duration_s' key not found in config.")
             .expect("Error: 'duration_s value invalid.");
+        let transition = config
+            .get("animation-settings", "transition")
+            .map(|transition| {
+                transition
+                    .parse::<Transition>()
+                    .expect("Error: 'transition' value invalid.")
+            })
+            .unwrap_or_default();
+        let transition_length = config
+            .getfloat("animation-settings", "transition_s")
+            .expect("Error: 'transition_s' value invalid.")
+            .map_or(1.0, |seconds| seconds as f32);
+        if transition_length < 0.0 {
+            panic!("Error: 'transition_s' must not be negative.");
+        }
+        let schedule_defaults = ScheduleSettings::default();
+        let schedule = ScheduleSettings {
+            order: config.get("animation-settings", "order").map_or(
+                schedule_defaults.order,
+                |order| {
+                    order
+                        .parse::<Order>()
+                        .expect("Error: 'order' value invalid.")
+                },
+            ),
+            switch_on: config.get("animation-settings", "switch_on").map_or(
+                schedule_defaults.switch_on,
+                |switch_on| {
+                    switch_on
+                        .parse::<SwitchOn>()
+                        .expect("Error: 'switch_on' value invalid.")
+                },
+            ),
+            max_wait: config
+                .getfloat("animation-settings", "max_wait_s")
+                .expect("Error: 'max_wait_s' value invalid.")
+                .map_or(schedule_defaults.max_wait, |seconds| seconds as f32),
+        };
+
+        let input = parse_input_section(&config, "input", &InputSettings::default());
+
+        let spectrum_source = config
+            .get("audio-settings", "spectrum_source")
+            .map(|source| {
+                source
+                    .parse::<SpectrumSource>()
+                    .expect("Error: 'spectrum_source' value invalid.")
+            })
+            .unwrap_or_default();
+        let resample = config
+            .getbool("audio-settings", "resample")
+            .expect("Error: 'resample' value invalid.")
+            .unwrap_or(AnalysisSettings::default().resample);
+
+        let defaults = AnalysisSettings::default();
+        let silence_threshold_db = config
+            .getfloat("idle", "threshold_db")
+            .expect("Error: 'threshold_db' value invalid.")
+            .map_or(defaults.silence_threshold_db, |db| db as f32);
+
+        let silence_hold_s = config
+            .getfloat("idle", "hold_s")
+            .expect("Error: 'hold_s' value invalid.")
+            .map_or(defaults.silence_hold_s, |hold| hold as f32);
+
+        let preprocess_defaults = PreprocessSettings::default();
+        let preprocess = PreprocessSettings {
+            dc_block: config
+                .getbool("preprocess", "dc_block")
+                .expect("Error: 'dc_block' value invalid.")
+                .unwrap_or(preprocess_defaults.dc_block),
+            gate_threshold_db: config
+                .getfloat("preprocess", "gate_threshold_db")
+                .expect("Error: 'gate_threshold_db' value invalid.")
+                .map(|db| db as f32),
+            gate_release_s: config
+                .getfloat("preprocess", "gate_release_s")
+                .expect("Error: 'gate_release_s' value invalid.")
+                .map_or(preprocess_defaults.gate_release_s, |release| release as f32),
+            pre_emphasis: config
+                .getfloat("preprocess", "pre_emphasis")
+                .expect("Error: 'pre_emphasis' value invalid.")
+                .map(|coefficient| {
+                    if coefficient >= 1.0 {
+                        panic!("Error: 'pre_emphasis' must be below 1.");
+                    }
+                    coefficient as f32
+                })
+                .filter(|coefficient| *coefficient > 0.0),
+        };
 
         let map = config.get_map().expect("Error parsing config.ini.");
+
+        // Stacked scenes from [layers.<name>] sections, `animation = z-order[, blend mode]`
+        let mut layers: HashMap<String, Vec<(String, BlendMode)>> = HashMap::new();
+        for (section, keys) in map.iter() {
+            let Some(scene) = section.strip_prefix("layers.") else {
+                continue;
+            };
+            if find_animator(scene).is_ok() {
+                panic!("Error: [layers.{scene}] has the name of an animation.");
+            }
+            let mut stack: Vec<(i32, String, BlendMode)> = keys
+                .iter()
+                .map(|(animation, value)| {
+                    let value = value.as_deref().unwrap_or_default();
+                    let (z, blend) = value.split_once(',').unwrap_or((value, "normal"));
+                    let z = z.trim().parse::<i32>().unwrap_or_else(|_| {
+                        panic!(
+                            "Error: [layers.{scene}] '{animation}' needs a z-order, e.g. '1, add'."
+                        )
+                    });
+                    let blend = blend
+                        .trim()
+                        .parse::<BlendMode>()
+                        .unwrap_or_else(|err| panic!("Error: [layers.{scene}] {err}."));
+                    (z, canonical_animation(animation, &HashMap::new()), blend)
+                })
+                .collect();
+            if stack.is_empty() {
+                panic!("Error: [layers.{scene}] needs at least one animation.");
+            }
+            stack.sort_by_key(|(z, _, _)| *z);
+            let stack = stack
+                .into_iter()
+                .map(|(_, animation, blend)| (animation, blend))
+                .collect();
+            layers.insert(scene.to_string(), stack);
+        }
+
+        let idle_screen = match config.get("idle", "animation") {
+            None => IdleScreen::Dim,
+            Some(name) if name == "dim" => IdleScreen::Dim,
+            Some(name) => IdleScreen::Animation(canonical_animation(&name, &layers)),
+        };
+
+        let idle_fps = config
+            .getfloat("idle", "fps")
+            .expect("Error: 'fps' value invalid.")
+            .map_or(4.0, |fps| fps as f32);
+        if idle_fps <= 0.0 {
+            panic!("Error: 'fps' must be greater than 0.");
+        }
+
+        let tap_defaults = TapSettings::default();
+        let tap = TapSettings {
+            mode: config
+                .get("capture", "mode")
+                .map_or(tap_defaults.mode, |mode| {
+                    mode.parse::<TapMode>()
+                        .expect("Error: capture 'mode' value invalid.")
+                }),
+            path: config
+                .get("capture", "path")
+                .filter(|path| !path.is_empty())
+                .unwrap_or(tap_defaults.path),
+            max_seconds: config
+                .getfloat("capture", "max_seconds")
+                .expect("Error: 'max_seconds' value invalid.")
+                .map_or(tap_defaults.max_seconds, |seconds| seconds as f32),
+            max_bytes: config
+                .getuint("capture", "max_mb")
+                .expect("Error: 'max_mb' value invalid.")
+                .map(|mb| mb * 1024 * 1024),
+        };
+
         let animators = map
             .get("animations")
             .expect("Coult not find 'animations' in config.");
         let mut animators: Vec<(String, i32)> = animators
             .iter()
-            .map(|(key, val)| (key.clone(), val.clone().unwrap().parse::<i32>().unwrap()))
+            .map(|(key, val)| {
+                let order = val.clone().unwrap().parse::<i32>().unwrap();
+                (canonical_animation(key, &layers), order)
+            })
             .filter(|(_key, val)| *val > 0)
             .collect();
         animators.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
         let animations: Vec<String> = animators.iter().map(|(key, _val)| key.clone()).collect();
-        Config {
+
+        let filters = map
+            .get("filters")
+            .map(parse_filter_section)
+            .unwrap_or_default();
+        let views: Vec<FilterView> = map
+            .iter()
+            .filter_map(|(section, keys)| {
+                section.strip_prefix("filters.").map(|name| FilterView {
+                    name: name.to_string(),
+                    filters: parse_filter_section(keys),
+                })
+            })
+            .collect();
+        let animation_views: HashMap<String, String> = map
+            .get("animation-filters")
+            .map(|keys| {
+                keys.iter()
+                    .filter_map(|(animation, view)| {
+                        Some((canonical_animation(animation, &layers), view.clone()?))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        for view in animation_views.values() {
+            if !views.iter().any(|defined| defined.name == *view) {
+                panic!("Error: filter view '{view}' has no [filters.{view}] section.");
+            }
+        }
+
+        let mut sources: Vec<(String, InputSettings)> = map
+            .keys()
+            .filter_map(|section| {
+                let name = section.strip_prefix("input.")?;
+                let source = parse_input_section(&config, section, &input);
+                Some((name.to_string(), source))
+            })
+            .collect();
+        sources.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut source_names = vec![String::from(MAIN_SOURCE)];
+        source_names.extend(sources.iter().map(|(name, _source)| name.clone()));
+        if !sources.is_empty() {
+            source_names.push(String::from(MIX_SOURCE));
+        }
+        let check_source = |name: &String| {
+            if !source_names.contains(name) {
+                panic!(
+                    "Error: unknown source '{name}', expected one of: {}.",
+                    source_names.join(", ")
+                );
+            }
+        };
+
+        let animation_durations = parse_animation_numbers(&map, "animation-durations", &layers);
+        if animation_durations
+            .values()
+            .any(|duration| *duration <= 0.0)
+        {
+            panic!("Error: [animation-durations] must be greater than 0.");
+        }
+        let animation_weights = parse_animation_numbers(&map, "animation-weights", &layers);
+        if animation_weights.values().any(|weight| *weight < 0.0) {
+            panic!("Error: [animation-weights] must not be negative.");
+        }
+
+        let animation_sources: HashMap<String, String> = map
+            .get("animation-sources")
+            .map(|keys| {
+                keys.iter()
+                    .filter_map(|(animation, source)| {
+                        Some((canonical_animation(animation, &layers), source.clone()?))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        animation_sources.values().for_each(check_source);
+
+        // [layout], or the older 'split' showing two sources side by side
+        let split_sources = config
+            .get("animation-settings", "split")
+            .filter(|split| !split.is_empty())
+            .map(|split| {
+                let (left, right) = split
+                    .split_once(',')
+                    .expect("Error: 'split' must name two sources, e.g. 'main, mic'.");
+                (left.trim().to_string(), right.trim().to_string())
+            });
+        let parse_region = |name: &str| {
+            let section = format!("region.{name}");
+            if !map.contains_key(&section) {
+                panic!("Error: region '{name}' has no [{section}] section.");
+            }
+            let source = config
+                .get(&section, "source")
+                .filter(|source| !source.is_empty());
+            if let Some(source) = &source {
+                check_source(source);
+            }
+            let size = config
+                .getfloat(&section, "size")
+                .expect("Error: region 'size' value invalid.")
+                .map_or(1.0, |size| size as f32);
+            if size <= 0.0 {
+                panic!("Error: [{section}] 'size' must be greater than 0.");
+            }
+            RegionSettings {
+                source,
+                animation: config
+                    .get(&section, "animation")
+                    .filter(|animation| !animation.is_empty())
+                    .map(|animation| canonical_animation(&animation, &layers)),
+                size,
+            }
+        };
+        let layout = match (map.contains_key("layout"), split_sources) {
+            (true, Some(_)) => {
+                panic!("Error: use either 'split' in [animation-settings] or a [layout] section.")
+            }
+            (false, Some((left, right))) => {
+                check_source(&left);
+                check_source(&right);
+                let region = |source: String| RegionSettings {
+                    source: Some(source),
+                    ..RegionSettings::default()
+                };
+                LayoutSettings {
+                    regions: vec![region(left), region(right)],
+                    ..LayoutSettings::default()
+                }
+            }
+            (false, None) => LayoutSettings::default(),
+            (true, None) => {
+                let defaults = LayoutSettings::default();
+                let mut split = config
+                    .get("layout", "split")
+                    .map_or(defaults.split, |split| {
+                        split
+                            .parse::<Split>()
+                            .expect("Error: 'split' value invalid.")
+                    });
+                if let Split::Grid(columns) = &mut split {
+                    if let Some(count) = config
+                        .getuint("layout", "columns")
+                        .expect("Error: 'columns' value invalid.")
+                    {
+                        if count == 0 {
+                            panic!("Error: 'columns' must be greater than 0.");
+                        }
+                        *columns = count as usize;
+                    }
+                }
+                let regions: Vec<RegionSettings> = config
+                    .get("layout", "regions")
+                    .expect("Error: [layout] needs 'regions', e.g. 'left, right'.")
+                    .split(',')
+                    .map(|name| parse_region(name.trim()))
+                    .collect();
+                LayoutSettings {
+                    split,
+                    regions,
+                    strip: config
+                        .get("layout", "strip")
+                        .filter(|strip| !strip.is_empty())
+                        .map(|strip| parse_region(strip.trim())),
+                    strip_height: config
+                        .getuint("layout", "strip_height")
+                        .expect("Error: 'strip_height' value invalid.")
+                        .map_or(defaults.strip_height, |height| height as usize),
+                }
+            }
+        };
+
+        let palette = Palette {
             color_1: Color::from_hex_string(color_1).expect("Invalid Hex!"),
             color_2: Color::from_hex_string(color_2).expect("Invalid Hex!"),
             color_3: Color::from_hex_string(color_3).expect("Invalid Hex!"),
-            bg_color: Color::from_hex_string(bg_color).expect("Invalid Hex!"),
-            bg_alt_color: Color::from_hex_string(bg_color_alt).expect("Invalid Hex!"),
+            bg: Color::from_hex_string(bg_color).expect("Invalid Hex!"),
+            bg_alt: Color::from_hex_string(bg_color_alt).expect("Invalid Hex!"),
+        };
+
+        // Sections may name the animation by an alias, e.g. [animation.sine-like]
+        let mut param_sections: HashMap<String, &HashMap<String, Option<String>>> = HashMap::new();
+        for (section, keys) in map.iter() {
+            if let Some(name) = section.strip_prefix("animation.") {
+                if param_sections
+                    .insert(canonical_animation(name, &HashMap::new()), keys)
+                    .is_some()
+                {
+                    panic!("Error: more than one [animation.<name>] section for '{name}'.");
+                }
+            }
+        }
+        let animation_params: HashMap<String, AnimatorParams> = REGISTRY
+            .iter()
+            .map(|info| {
+                let section = param_sections.get(info.name).copied();
+                let params = AnimatorParams::parse(info.params, section, &palette)
+                    .unwrap_or_else(|err| panic!("Error: [animation.{}] {err}.", info.name));
+                (info.name.to_string(), params)
+            })
+            .collect();
+
+        Config {
+            color_1: palette.color_1,
+            color_2: palette.color_2,
+            color_3: palette.color_3,
+            bg_color: palette.bg,
+            bg_alt_color: palette.bg_alt,
             animation_length,
+            transition,
+            transition_length,
+            schedule,
             animations,
+            animation_views,
+            animation_sources,
+            animation_durations,
+            animation_weights,
+            layers,
+            animation_params,
+            layout,
+            input,
+            sources,
+            analysis: AnalysisSettings {
+                spectrum_source,
+                resample,
+                silence_threshold_db,
+                silence_hold_s,
+                preprocess,
+                filters,
+                views,
+            },
+            idle_screen,
+            idle_fps,
+            tap,
         }
     }
 }
+
+// A number per animation from `section`, keyed by registry name
+fn parse_animation_numbers(
+    map: &HashMap<String, HashMap<String, Option<String>>>,
+    section: &str,
+    layers: &HashMap<String, Vec<(String, BlendMode)>>,
+) -> HashMap<String, f32> {
+    map.get(section)
+        .map(|keys| {
+            keys.iter()
+                .filter_map(|(animation, value)| {
+                    let value = value.as_ref()?.parse::<f32>().unwrap_or_else(|_| {
+                        panic!("Error: [{section}] '{animation}' value invalid.")
+                    });
+                    Some((canonical_animation(animation, layers), value))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Registry name of an animation given by name or alias in the config,
+// names of [layers.<name>] scenes are kept as they are
+fn canonical_animation(name: &str, layers: &HashMap<String, Vec<(String, BlendMode)>>) -> String {
+    if layers.contains_key(name) {
+        return name.to_string();
+    }
+    match find_animator(name) {
+        Ok(info) => info.name.to_string(),
+        Err(err) => panic!("Error: {err}"),
+    }
+}
+
+// Filter stages in key order, so 'stage_1, stage_2, ...' run in sequence
+fn parse_filter_section(section: &HashMap<String, Option<String>>) -> Vec<FilterSpec> {
+    let mut stages: Vec<(&String, &String)> = section
+        .iter()
+        .filter_map(|(key, value)| Some((key, value.as_ref()?)))
+        .collect();
+    stages.sort_by_key(|(key, _value)| {
+        let digits = key.trim_start_matches(|c: char| !c.is_ascii_digit());
+        (digits.parse::<u32>().unwrap_or(u32::MAX), (*key).clone())
+    });
+    stages
+        .iter()
+        .map(|(key, value)| {
+            value
+                .parse::<FilterSpec>()
+                .unwrap_or_else(|err| panic!("Error: filter '{key}' invalid. {err}"))
+        })
+        .collect()
+}
+
+// Keys of [input], or of an [input.<name>] source falling back to [input] and [network]
+fn parse_input_section(config: &Ini, section: &str, defaults: &InputSettings) -> InputSettings {
+    InputSettings {
+        backend: config
+            .get(section, "backend")
+            .map_or(defaults.backend, |backend| {
+                backend
+                    .parse::<Backend>()
+                    .expect("Error: 'backend' value invalid.")
+            }),
+        host: config
+            .get(section, "host")
+            .map_or(defaults.host.clone(), |host| {
+                Some(host).filter(|host| !host.is_empty())
+            }),
+        device: config
+            .get(section, "device")
+            .map_or(defaults.device.clone(), |device| {
+                Some(device).filter(|device| !device.is_empty())
+            }),
+        capture: config
+            .get(section, "capture")
+            .map_or(defaults.capture, |capture| {
+                capture
+                    .parse::<CaptureMode>()
+                    .expect("Error: 'capture' value invalid.")
+            }),
+        jack_ports: config
+            .getuint(section, "jack_ports")
+            .expect("Error: 'jack_ports' value invalid.")
+            .map_or(defaults.jack_ports, |ports| ports as usize),
+        // [input] reads its stream format from [network], sources may override it inline
+        network: match section {
+            "input" => parse_network_section(config, "network", &defaults.network),
+            _ => parse_network_section(config, section, &defaults.network),
+        },
+    }
+}
+
+// Describes the stream the network backend listens for
+fn parse_network_section(
+    config: &Ini,
+    section: &str,
+    defaults: &NetworkSettings,
+) -> NetworkSettings {
+    NetworkSettings {
+        listen: config
+            .get(section, "listen")
+            .filter(|listen| !listen.is_empty())
+            .unwrap_or(defaults.listen.clone()),
+        protocol: config
+            .get(section, "protocol")
+            .map_or(defaults.protocol, |protocol| {
+                protocol
+                    .parse::<Protocol>()
+                    .expect("Error: 'protocol' value invalid.")
+            }),
+        format: config
+            .get(section, "format")
+            .map_or(defaults.format, |format| {
+                format
+                    .parse::<SampleFormat>()
+                    .expect("Error: 'format' value invalid.")
+            }),
+        rate: config
+            .getuint(section, "rate")
+            .expect("Error: 'rate' value invalid.")
+            .map_or(defaults.rate, |rate| rate as u32),
+        channels: config
+            .getuint(section, "channels")
+            .expect("Error: 'channels' value invalid.")
+            .map_or(defaults.channels, |channels| channels as usize),
+        jitter_ms: config
+            .getuint(section, "jitter_ms")
+            .expect("Error: 'jitter_ms' value invalid.")
+            .map_or(defaults.jitter_ms, |jitter_ms| jitter_ms as u32),
+    }
+}
@@ -0,0 +1,413 @@
+// Records the published AudioFeatures to a file and plays them back without an audio backend.
+//
+// File layout, all values little endian:
+//   header: b"AAVF", u32 version, u32 fft bin count, u32 spectrum size, u32 waveform size
+//   records: f32 seconds since recording started, features,
+//            u16 view count, per view: u16 name length, utf-8 name, features
+// where features are u8 silent, f32 silence duration, the smoothed values of
+// rms, zcr, lo, mi, hi, fft bins and spectrum, then the spectrum frequencies and
+// the waveform samples.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::audio_processing::{AnalysisSettings, AudioFeatures, AudioProcessBuffer};
+use crate::filters::FilterView;
+use crate::input::InputStatus;
+
+const MAGIC: &[u8; 4] = b"AAVF";
+const VERSION: u32 = 1;
+
+// Features published at one point in time, with those of each filtered view
+pub struct FeatureSnapshot {
+    pub time: f32,
+    pub features: AudioFeatures,
+    pub views: Vec<(String, AudioFeatures)>,
+}
+
+// Hands snapshots to a writer thread, so the audio thread never waits on the disk
+pub struct FeatureRecorder {
+    sender: mpsc::Sender<FeatureSnapshot>,
+    start: Instant,
+    // Why the writer thread stopped, shown by the UI since the terminal is taken
+    errors: mpsc::Receiver<String>,
+}
+
+impl FeatureRecorder {
+    pub fn create(path: &str) -> Result<FeatureRecorder, anyhow::Error> {
+        let file = File::create(path)
+            .map_err(|err| anyhow::Error::msg(format!("Failed to create '{path}': {err}")))?;
+        let mut writer = BufWriter::new(file);
+        write_header(&mut writer)?;
+        writer.flush()?;
+
+        let (sender, receiver) = mpsc::channel::<FeatureSnapshot>();
+        let (error_sender, errors) = mpsc::channel();
+        let path = path.to_string();
+        thread::spawn(move || {
+            for snapshot in receiver {
+                // flush every record so a crash still leaves a usable file
+                let written = write_snapshot(&mut writer, &snapshot).and_then(|_| writer.flush());
+                if let Err(err) = written {
+                    let _ =
+                        error_sender.send(format!("Failed to record features to '{path}': {err}"));
+                    return;
+                }
+            }
+        });
+
+        Ok(FeatureRecorder {
+            sender,
+            start: Instant::now(),
+            errors,
+        })
+    }
+
+    pub fn record(&self, features: &AudioFeatures, views: Vec<(String, AudioFeatures)>) {
+        let _ = self.sender.send(FeatureSnapshot {
+            time: self.start.elapsed().as_secs_f32(),
+            features: *features,
+            views,
+        });
+    }
+
+    // The failure that stopped the recording, once
+    pub fn take_error(&self) -> Option<String> {
+        self.errors.try_recv().ok()
+    }
+}
+
+// Publishes the recorded snapshots on their original schedule, like an input backend would.
+// The analysis buffer gets one view per view in the recording.
+pub fn replay(path: &str) -> Result<Arc<Mutex<AudioProcessBuffer>>, anyhow::Error> {
+    let file = File::open(path)
+        .map_err(|err| anyhow::Error::msg(format!("Failed to open '{path}': {err}")))?;
+    let mut reader = BufReader::new(file);
+    read_header(&mut reader)?;
+    let first = read_snapshot(&mut reader)?
+        .ok_or(anyhow::Error::msg(format!("'{path}' contains no features")))?;
+
+    let settings = AnalysisSettings {
+        views: first
+            .views
+            .iter()
+            .map(|(name, _features)| FilterView {
+                name: name.clone(),
+                filters: vec![],
+            })
+            .collect(),
+        ..Default::default()
+    };
+    let process_buffer_writer = Arc::new(Mutex::new(AudioProcessBuffer::new(settings)));
+    let process_buffer_reader = process_buffer_writer.clone();
+    if let Ok(mut buffer) = process_buffer_writer.lock() {
+        buffer.status = InputStatus::Connected(format!("replaying {path}"));
+    }
+
+    let path = path.to_string();
+    thread::spawn(move || {
+        let start = Instant::now();
+        let mut next = Some(first);
+        // shown once the replay stops, the terminal belongs to the UI
+        let mut end_status = String::from("replay finished");
+        while let Some(snapshot) = next {
+            let due = Duration::from_secs_f32(snapshot.time.max(0.0));
+            thread::sleep(due.saturating_sub(start.elapsed()));
+            if let Ok(mut buffer) = process_buffer_writer.lock() {
+                buffer.publish(&snapshot.features, &snapshot.views);
+            }
+
+            next = match read_snapshot(&mut reader) {
+                Ok(snapshot) => snapshot,
+                Err(err) => {
+                    end_status = format!("Failed to read features from '{path}': {err}");
+                    None
+                }
+            };
+        }
+        if let Ok(mut buffer) = process_buffer_writer.lock() {
+            buffer.status = InputStatus::Disconnected(end_status);
+        }
+    });
+
+    Ok(process_buffer_reader)
+}
+
+fn write_header(writer: &mut impl Write) -> std::io::Result<()> {
+    let features = AudioFeatures::default();
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&(features.fft_bins.len() as u32).to_le_bytes())?;
+    writer.write_all(&(features.spectrum.len() as u32).to_le_bytes())?;
+    writer.write_all(&(features.waveform.len() as u32).to_le_bytes())
+}
+
+fn read_header(reader: &mut impl Read) -> Result<(), anyhow::Error> {
+    let features = AudioFeatures::default();
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(anyhow::Error::msg("Not a recorded feature file"));
+    }
+    let version = read_u32(reader)?;
+    if version != VERSION {
+        return Err(anyhow::Error::msg(format!(
+            "Unsupported feature file version {version}"
+        )));
+    }
+    let fft_bins = read_u32(reader)? as usize;
+    let spectrum_size = read_u32(reader)? as usize;
+    if fft_bins != features.fft_bins.len() || spectrum_size != features.spectrum.len() {
+        return Err(anyhow::Error::msg(format!(
+            "Feature file has {fft_bins} fft bins and {spectrum_size} spectrum bands, expected {} and {}",
+            features.fft_bins.len(),
+            features.spectrum.len()
+        )));
+    }
+    let waveform_size = read_u32(reader)? as usize;
+    if waveform_size != features.waveform.len() {
+        return Err(anyhow::Error::msg(format!(
+            "Feature file has {waveform_size} waveform samples, expected {}",
+            features.waveform.len()
+        )));
+    }
+    Ok(())
+}
+
+fn write_snapshot(writer: &mut impl Write, snapshot: &FeatureSnapshot) -> std::io::Result<()> {
+    writer.write_all(&snapshot.time.to_le_bytes())?;
+    write_features(writer, &snapshot.features)?;
+    writer.write_all(&(snapshot.views.len() as u16).to_le_bytes())?;
+    for (name, features) in snapshot.views.iter() {
+        writer.write_all(&(name.len() as u16).to_le_bytes())?;
+        writer.write_all(name.as_bytes())?;
+        write_features(writer, features)?;
+    }
+    Ok(())
+}
+
+// None at the end of the file. A record cut short, e.g. by a crash while recording,
+// is the end of the file as well.
+fn read_snapshot(reader: &mut impl Read) -> Result<Option<FeatureSnapshot>, anyhow::Error> {
+    match read_record(reader) {
+        Err(err)
+            if err
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(|err| err.kind() == ErrorKind::UnexpectedEof) =>
+        {
+            Ok(None)
+        }
+        result => result.map(Some),
+    }
+}
+
+fn read_record(reader: &mut impl Read) -> Result<FeatureSnapshot, anyhow::Error> {
+    let time = read_f32(reader)?;
+    let features = read_features(reader)?;
+    let view_count = read_u16(reader)?;
+    let mut views = Vec::with_capacity(view_count as usize);
+    for _ in 0..view_count {
+        let mut name = vec![0; read_u16(reader)? as usize];
+        reader.read_exact(&mut name)?;
+        views.push((String::from_utf8(name)?, read_features(reader)?));
+    }
+    Ok(FeatureSnapshot {
+        time,
+        features,
+        views,
+    })
+}
+
+fn write_features(writer: &mut impl Write, features: &AudioFeatures) -> std::io::Result<()> {
+    writer.write_all(&[features.silent as u8])?;
+    writer.write_all(&features.silence_duration.to_le_bytes())?;
+    let values = [
+        &features.root_mean_squared,
+        &features.zero_crossing_rate,
+        &features.lo,
+        &features.mi,
+        &features.hi,
+    ]
+    .into_iter()
+    .chain(features.fft_bins.iter())
+    .chain(features.spectrum.iter());
+    for value in values {
+        writer.write_all(&value.smoothed_val.to_le_bytes())?;
+    }
+    for sample in features
+        .spectrum_freqs
+        .iter()
+        .chain(features.waveform.iter())
+    {
+        writer.write_all(&sample.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_features(reader: &mut impl Read) -> std::io::Result<AudioFeatures> {
+    let mut features = AudioFeatures::default();
+    let mut silent = [0; 1];
+    reader.read_exact(&mut silent)?;
+    features.silent = silent[0] != 0;
+    features.silence_duration = read_f32(reader)?;
+    let values = [
+        &mut features.root_mean_squared,
+        &mut features.zero_crossing_rate,
+        &mut features.lo,
+        &mut features.mi,
+        &mut features.hi,
+    ]
+    .into_iter()
+    .chain(features.fft_bins.iter_mut())
+    .chain(features.spectrum.iter_mut());
+    for value in values {
+        value.smoothed_val = read_f32(reader)?;
+    }
+    for freq in features.spectrum_freqs.iter_mut() {
+        *freq = read_f32(reader)?;
+    }
+    for sample in features.waveform.iter_mut() {
+        *sample = read_f32(reader)?;
+    }
+    Ok(features)
+}
+
+fn read_f32(reader: &mut impl Read) -> std::io::Result<f32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(f32::from_le_bytes(bytes))
+}
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u16(reader: &mut impl Read) -> std::io::Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // Features with every recorded value set to something recognizable
+    fn sample_features(seed: f32) -> AudioFeatures {
+        let mut features = AudioFeatures {
+            silent: true,
+            silence_duration: seed,
+            ..Default::default()
+        };
+        features.root_mean_squared.smoothed_val = seed + 0.1;
+        features.zero_crossing_rate.smoothed_val = seed + 0.2;
+        features.lo.smoothed_val = seed + 0.3;
+        features.mi.smoothed_val = seed + 0.4;
+        features.hi.smoothed_val = seed + 0.5;
+        for (i, bin) in features.fft_bins.iter_mut().enumerate() {
+            bin.smoothed_val = seed * i as f32;
+        }
+        for (i, band) in features.spectrum.iter_mut().enumerate() {
+            band.smoothed_val = seed / (i + 1) as f32;
+        }
+        for (i, freq) in features.spectrum_freqs.iter_mut().enumerate() {
+            *freq = 60.0 * i as f32;
+        }
+        for (i, sample) in features.waveform.iter_mut().enumerate() {
+            *sample = (i as f32 * seed).sin();
+        }
+        features
+    }
+
+    fn assert_same(a: &AudioFeatures, b: &AudioFeatures) {
+        assert_eq!(a.silent, b.silent);
+        assert_eq!(a.silence_duration, b.silence_duration);
+        let values = |f: &AudioFeatures| {
+            [f.root_mean_squared, f.zero_crossing_rate, f.lo, f.mi, f.hi]
+                .iter()
+                .chain(f.fft_bins.iter())
+                .chain(f.spectrum.iter())
+                .map(|value| value.smoothed_val)
+                .collect::<Vec<f32>>()
+        };
+        assert_eq!(values(a), values(b));
+        assert_eq!(a.spectrum_freqs, b.spectrum_freqs);
+        assert_eq!(a.waveform, b.waveform);
+    }
+
+    fn recording(snapshots: &[FeatureSnapshot]) -> Vec<u8> {
+        let mut bytes = vec![];
+        write_header(&mut bytes).unwrap();
+        for snapshot in snapshots {
+            write_snapshot(&mut bytes, snapshot).unwrap();
+        }
+        bytes
+    }
+
+    fn snapshots() -> Vec<FeatureSnapshot> {
+        vec![
+            FeatureSnapshot {
+                time: 0.0,
+                features: sample_features(0.25),
+                views: vec![],
+            },
+            FeatureSnapshot {
+                time: 0.017,
+                features: sample_features(0.5),
+                views: vec![(String::from("bass"), sample_features(0.75))],
+            },
+        ]
+    }
+
+    #[test]
+    fn recording_reads_back() {
+        let snapshots = snapshots();
+        let mut reader = Cursor::new(recording(&snapshots));
+        read_header(&mut reader).unwrap();
+        for expected in snapshots.iter() {
+            let snapshot = read_snapshot(&mut reader).unwrap().unwrap();
+            assert_eq!(snapshot.time, expected.time);
+            assert_same(&snapshot.features, &expected.features);
+            assert_eq!(snapshot.views.len(), expected.views.len());
+            for ((name, features), (expected_name, expected_features)) in
+                snapshot.views.iter().zip(expected.views.iter())
+            {
+                assert_eq!(name, expected_name);
+                assert_same(features, expected_features);
+            }
+        }
+        assert!(read_snapshot(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn truncated_final_record_ends_the_recording() {
+        let snapshots = snapshots();
+        let mut bytes = recording(&snapshots);
+        bytes.truncate(bytes.len() - 100);
+        let mut reader = Cursor::new(bytes);
+        read_header(&mut reader).unwrap();
+        let first = read_snapshot(&mut reader).unwrap().unwrap();
+        assert_same(&first.features, &snapshots[0].features);
+        assert!(read_snapshot(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(read_header(&mut Cursor::new(b"RIFF\0\0\0\0".to_vec())).is_err());
+        let mut bytes = recording(&[]);
+        bytes[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(read_header(&mut Cursor::new(bytes)).is_err());
+        let mut bytes = recording(&[]);
+        bytes[8..12].copy_from_slice(&1u32.to_le_bytes());
+        assert!(read_header(&mut Cursor::new(bytes)).is_err());
+        let mut bytes = recording(&[]);
+        bytes[16..20].copy_from_slice(&1u32.to_le_bytes());
+        assert!(read_header(&mut Cursor::new(bytes)).is_err());
+    }
+}
@@ -0,0 +1,320 @@
+// Biquad filters (RBJ audio EQ cookbook) that can be chained in front of the analysis,
+// e.g. to only visualize the bass line or the vocal range.
+
+use std::f32::consts::PI;
+use std::str::FromStr;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum FilterType {
+    LowPass,
+    HighPass,
+    BandPass,
+    Notch,
+    Peak,
+    LowShelf,
+    HighShelf,
+}
+
+impl FromStr for FilterType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lowpass" => Ok(FilterType::LowPass),
+            "highpass" => Ok(FilterType::HighPass),
+            "bandpass" => Ok(FilterType::BandPass),
+            "notch" => Ok(FilterType::Notch),
+            "peak" => Ok(FilterType::Peak),
+            "lowshelf" => Ok(FilterType::LowShelf),
+            "highshelf" => Ok(FilterType::HighShelf),
+            _ => Err(format!("Unknown filter type '{s}'")),
+        }
+    }
+}
+
+// One configured stage: "type, frequency, Q, gain"
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct FilterSpec {
+    pub filter_type: FilterType,
+    pub frequency: f32,
+    pub q: f32,
+    pub gain_db: f32,
+}
+
+impl FromStr for FilterSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split(',').map(|field| field.trim()).collect();
+        if fields.is_empty() || fields.len() > 4 {
+            return Err(format!(
+                "Invalid filter '{s}', expected 'type, frequency, Q, gain'"
+            ));
+        }
+        let number = |idx: usize, default: f32| -> Result<f32, String> {
+            match fields.get(idx) {
+                Some(field) => field
+                    .parse::<f32>()
+                    .map_err(|_| format!("Invalid number '{field}' in filter '{s}'")),
+                None => Ok(default),
+            }
+        };
+        let spec = FilterSpec {
+            filter_type: fields[0].parse()?,
+            frequency: number(1, 1_000.0)?,
+            q: number(2, std::f32::consts::FRAC_1_SQRT_2)?,
+            gain_db: number(3, 0.0)?,
+        };
+        if spec.frequency <= 0.0 || spec.q <= 0.0 {
+            return Err(format!("Filter '{s}' needs a positive frequency and Q"));
+        }
+        Ok(spec)
+    }
+}
+
+// Transposed direct form II biquad
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    pub fn new(spec: FilterSpec, sample_rate: f32) -> Biquad {
+        // keep the center frequency below nyquist
+        let frequency = spec.frequency.min(sample_rate * 0.49);
+        let w0 = 2.0 * PI * frequency / sample_rate;
+        let (sin_w0, cos_w0) = w0.sin_cos();
+        let alpha = sin_w0 / (2.0 * spec.q);
+        let a = 10f32.powf(spec.gain_db / 40.0);
+        let sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+
+        let (b0, b1, b2, a0, a1, a2) = match spec.filter_type {
+            FilterType::LowPass => (
+                (1.0 - cos_w0) / 2.0,
+                1.0 - cos_w0,
+                (1.0 - cos_w0) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+            FilterType::HighPass => (
+                (1.0 + cos_w0) / 2.0,
+                -(1.0 + cos_w0),
+                (1.0 + cos_w0) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+            FilterType::BandPass => (alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos_w0, 1.0 - alpha),
+            FilterType::Notch => (
+                1.0,
+                -2.0 * cos_w0,
+                1.0,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+            FilterType::Peak => (
+                1.0 + alpha * a,
+                -2.0 * cos_w0,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos_w0,
+                1.0 - alpha / a,
+            ),
+            FilterType::LowShelf => (
+                a * ((a + 1.0) - (a - 1.0) * cos_w0 + sqrt_a_alpha),
+                2.0 * a * ((a - 1.0) - (a + 1.0) * cos_w0),
+                a * ((a + 1.0) - (a - 1.0) * cos_w0 - sqrt_a_alpha),
+                (a + 1.0) + (a - 1.0) * cos_w0 + sqrt_a_alpha,
+                -2.0 * ((a - 1.0) + (a + 1.0) * cos_w0),
+                (a + 1.0) + (a - 1.0) * cos_w0 - sqrt_a_alpha,
+            ),
+            FilterType::HighShelf => (
+                a * ((a + 1.0) + (a - 1.0) * cos_w0 + sqrt_a_alpha),
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos_w0),
+                a * ((a + 1.0) + (a - 1.0) * cos_w0 - sqrt_a_alpha),
+                (a + 1.0) - (a - 1.0) * cos_w0 + sqrt_a_alpha,
+                2.0 * ((a - 1.0) - (a + 1.0) * cos_w0),
+                (a + 1.0) - (a - 1.0) * cos_w0 - sqrt_a_alpha,
+            ),
+        };
+
+        Biquad {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        let out = self.b0 * sample + self.z1;
+        self.z1 = self.b1 * sample - self.a1 * out + self.z2;
+        self.z2 = self.b2 * sample - self.a2 * out;
+        out
+    }
+}
+
+// Stages run in order, an empty chain passes samples through untouched
+pub struct FilterChain {
+    stages: Vec<Biquad>,
+}
+
+impl FilterChain {
+    pub fn new(specs: &[FilterSpec], sample_rate: f32) -> FilterChain {
+        FilterChain {
+            stages: specs
+                .iter()
+                .map(|spec| Biquad::new(*spec, sample_rate))
+                .collect(),
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        self.stages
+            .iter_mut()
+            .fold(sample, |sample, stage| stage.process(sample))
+    }
+}
+
+// A named, separately filtered copy of the signal that animations can ask for
+#[derive(Clone)]
+pub struct FilterView {
+    pub name: String,
+    pub filters: Vec<FilterSpec>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: f32 = 48_000.0;
+
+    fn spec(s: &str) -> FilterSpec {
+        s.parse().unwrap()
+    }
+
+    // Gain in dB of a steady sine at `freq`, measured after the filter settled
+    fn gain_db(mut process: impl FnMut(f32) -> f32, freq: f32) -> f32 {
+        let sine = |n: usize| (2.0 * PI * freq * n as f32 / RATE).sin();
+        let settle = RATE as usize / 2;
+        let (mut input, mut output) = (0.0, 0.0);
+        for n in 0..settle + RATE as usize / 2 {
+            let out = process(sine(n));
+            if n >= settle {
+                input += sine(n).powi(2);
+                output += out.powi(2);
+            }
+        }
+        10.0 * (output / input).log10()
+    }
+
+    fn response(s: &str, freq: f32) -> f32 {
+        let mut biquad = Biquad::new(spec(s), RATE);
+        gain_db(|sample| biquad.process(sample), freq)
+    }
+
+    fn assert_near(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{actual}dB, expected {expected}dB"
+        );
+    }
+
+    #[test]
+    fn lowpass_attenuates_above_cutoff() {
+        assert_near(response("lowpass, 1000", 100.0), 0.0, 0.1);
+        assert_near(response("lowpass, 1000", 1000.0), -3.0, 0.2);
+        // 12dB per octave, three octaves up
+        assert!(response("lowpass, 1000", 8000.0) < -30.0);
+    }
+
+    #[test]
+    fn highpass_passes_above_cutoff() {
+        assert_near(response("highpass, 1000", 8000.0), 0.0, 0.1);
+        assert_near(response("highpass, 1000", 1000.0), -3.0, 0.2);
+        assert!(response("highpass, 1000", 125.0) < -30.0);
+    }
+
+    #[test]
+    fn bandpass_and_notch_center_on_frequency() {
+        assert_near(response("bandpass, 1000, 2", 1000.0), 0.0, 0.1);
+        assert!(response("bandpass, 1000, 2", 100.0) < -20.0);
+        assert!(response("notch, 1000, 2", 1000.0) < -30.0);
+        assert_near(response("notch, 1000, 2", 100.0), 0.0, 0.1);
+    }
+
+    #[test]
+    fn peak_and_shelf_gains_land_on_request() {
+        assert_near(response("peak, 1000, 1, 6", 1000.0), 6.0, 0.1);
+        assert_near(response("peak, 1000, 1, -9", 1000.0), -9.0, 0.1);
+        assert_near(response("peak, 1000, 1, 6", 20.0), 0.0, 0.1);
+        assert_near(response("lowshelf, 200, 0.707, 6", 20.0), 6.0, 0.2);
+        assert_near(response("lowshelf, 200, 0.707, 6", 8000.0), 0.0, 0.1);
+        assert_near(response("highshelf, 4000, 0.707, -6", 20_000.0), -6.0, 0.3);
+        assert_near(response("highshelf, 4000, 0.707, -6", 100.0), 0.0, 0.1);
+    }
+
+    #[test]
+    fn chain_runs_every_stage() {
+        let mut chain = FilterChain::new(&[], RATE);
+        assert_eq!(chain.process(0.25), 0.25);
+
+        let single = response("lowpass, 1000", 4000.0);
+        let mut chain = FilterChain::new(&[spec("lowpass, 1000"), spec("lowpass, 1000")], RATE);
+        assert_near(
+            gain_db(|sample| chain.process(sample), 4000.0),
+            2.0 * single,
+            0.1,
+        );
+    }
+
+    #[test]
+    fn parses_filter_specs() {
+        assert_eq!(
+            spec("peak, 2500, 1.4, -3"),
+            FilterSpec {
+                filter_type: FilterType::Peak,
+                frequency: 2500.0,
+                q: 1.4,
+                gain_db: -3.0,
+            }
+        );
+        // frequency, Q and gain have defaults
+        assert_eq!(
+            spec("highpass"),
+            FilterSpec {
+                filter_type: FilterType::HighPass,
+                frequency: 1000.0,
+                q: std::f32::consts::FRAC_1_SQRT_2,
+                gain_db: 0.0,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_bad_filter_specs() {
+        for bad in [
+            "",
+            "wobble, 100",
+            "LowPass, 100",
+            "lowpass, fast",
+            "lowpass, 100, q",
+            "lowpass, 0",
+            "lowpass, -100",
+            "lowpass, 100, 0",
+            "peak, 100, 1, 6, 2",
+        ] {
+            assert!(bad.parse::<FilterSpec>().is_err(), "'{bad}' was accepted");
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use cpal::{
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+    SizedSample, Stream, StreamConfig, StreamError,
+};
+use std::sync::{Arc, Mutex};
+
+use crate::audio_formats::ToF32Sample;
+use crate::audio_processing::{AnalysisSettings, AudioProcessBuffer};
+use crate::input::{CaptureMode, InputSettings, InputStatus};
+
+// Only WASAPI can record what an output device plays (loopback).
+// Elsewhere use an input device, e.g. the ALSA snd-aloop capture side 'hw:CARD=Loopback,DEV=1'.
+const SUPPORTS_LOOPBACK: bool = cfg!(target_os = "windows");
+
+pub fn connect(
+    settings: AnalysisSettings,
+    input: &InputSettings,
+) -> Result<(Arc<Mutex<AudioProcessBuffer>>, Stream), anyhow::Error> {
+    let process_buffer_writer = Arc::new(Mutex::new(AudioProcessBuffer::new(settings)));
+    let process_buffer_reader = process_buffer_writer.clone();
+
+    let host = find_host(input.host.as_deref())?;
+
+    // Output devices are captured in loopback mode, input devices directly
+    let (device, is_output) = match (&input.device, input.capture) {
+        (Some(name), _) => find_device(&host, name)?,
+        (None, CaptureMode::Monitor) if SUPPORTS_LOOPBACK => (
+            host.default_output_device()
+                .ok_or(anyhow::Error::msg("Failed to get default output device"))?,
+            true,
+        ),
+        (None, _) => (
+            host.default_input_device()
+                .ok_or(anyhow::Error::msg("Failed to get default input device"))?,
+            false,
+        ),
+    };
+    let config = if is_output {
+        device.default_output_config()?
+    } else {
+        device.default_input_config()?
+    };
+
+    let sample_format = config.sample_format();
+    let config: StreamConfig = config.into();
+    let writer = process_buffer_writer;
+    let stream = match sample_format {
+        cpal::SampleFormat::I8 => build_stream::<i8>(&device, &config, writer)?,
+        cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, writer)?,
+        cpal::SampleFormat::I32 => build_stream::<i32>(&device, &config, writer)?,
+        cpal::SampleFormat::I64 => build_stream::<i64>(&device, &config, writer)?,
+        cpal::SampleFormat::U8 => build_stream::<u8>(&device, &config, writer)?,
+        cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, writer)?,
+        cpal::SampleFormat::U32 => build_stream::<u32>(&device, &config, writer)?,
+        cpal::SampleFormat::U64 => build_stream::<u64>(&device, &config, writer)?,
+        cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, writer)?,
+        cpal::SampleFormat::F64 => build_stream::<f64>(&device, &config, writer)?,
+        sample_format => {
+            return Err(anyhow::Error::msg(format!(
+                "Unsupported sample format '{sample_format}'"
+            )))
+        }
+    };
+
+    stream.play()?;
+    if let Ok(mut buffer) = process_buffer_reader.lock() {
+        buffer.set_input_rate(config.sample_rate.0);
+        buffer.status = InputStatus::Connected(format!(
+            "{} ({}Hz, {} channels)",
+            device.name().unwrap_or_default(),
+            config.sample_rate.0,
+            config.channels
+        ));
+    }
+    Ok((process_buffer_reader, stream))
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    processing_buffer: Arc<Mutex<AudioProcessBuffer>>,
+) -> Result<Stream, anyhow::Error>
+where
+    T: SizedSample + ToF32Sample,
+{
+    let channels = config.channels as usize;
+    Ok(device.build_input_stream(
+        config,
+        move |audio_buffer: &[T], _: &_| {
+            cpal_audio_callback(audio_buffer, channels, &processing_buffer)
+        },
+        cpal_err_callback,
+        None,
+    )?)
+}
+
+// Picks a host by name (e.g. 'ALSA', 'JACK', 'WASAPI'), the platform default when None
+fn find_host(name: Option<&str>) -> Result<cpal::Host, anyhow::Error> {
+    let Some(name) = name else {
+        return Ok(cpal::default_host());
+    };
+    let host_id = cpal::available_hosts()
+        .into_iter()
+        .find(|host_id| host_id.name().eq_ignore_ascii_case(name))
+        .ok_or(anyhow::Error::msg(format!(
+            "Unknown audio host '{name}', available: {}",
+            cpal::available_hosts()
+                .iter()
+                .map(|host_id| host_id.name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )))?;
+    Ok(cpal::host_from_id(host_id)?)
+}
+
+fn find_device(host: &cpal::Host, name: &str) -> Result<(cpal::Device, bool), anyhow::Error> {
+    if SUPPORTS_LOOPBACK {
+        if let Some(device) = host
+            .output_devices()?
+            .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+        {
+            return Ok((device, true));
+        }
+    }
+    if let Some(device) = host
+        .input_devices()?
+        .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+    {
+        return Ok((device, false));
+    }
+    Err(anyhow::Error::msg(format!(
+        "Unknown audio device '{name}', see --list-devices"
+    )))
+}
+
+pub fn list_devices(input: &InputSettings) -> Result<(), anyhow::Error> {
+    let host = find_host(input.host.as_deref())?;
+    let default_output = host.default_output_device().and_then(|d| d.name().ok());
+    let default_input = host.default_input_device().and_then(|d| d.name().ok());
+
+    println!(
+        "Hosts: {} (using {})",
+        cpal::available_hosts()
+            .iter()
+            .map(|host_id| host_id.name())
+            .collect::<Vec<_>>()
+            .join(", "),
+        host.id().name()
+    );
+
+    if SUPPORTS_LOOPBACK {
+        println!("Output devices (captured in loopback):");
+        for device in host.output_devices()? {
+            let name = device.name()?;
+            let note = if Some(&name) == default_output.as_ref() {
+                " (default)"
+            } else {
+                ""
+            };
+            println!("  {name}{note}");
+        }
+    }
+
+    println!("Input devices:");
+    for device in host.input_devices()? {
+        let name = device.name()?;
+        let note = if Some(&name) == default_input.as_ref() {
+            " (default)"
+        } else {
+            ""
+        };
+        println!("  {name}{note}");
+    }
+    Ok(())
+}
+
+pub fn cpal_audio_callback<T: ToF32Sample>(
+    input_buffer: &[T],
+    channels: usize,
+    processing_buffer: &Arc<Mutex<AudioProcessBuffer>>,
+) {
+    // write to process buffer in mono, converting each sample to f32 in place
+    if let Ok(mut buffer) = processing_buffer.try_lock() {
+        for frame in input_buffer.chunks_exact(channels.max(1)) {
+            let sum: f32 = frame.iter().map(|sample| sample.to_f32_sample()).sum();
+            buffer.push(sum / (frame.len() as f32));
+        }
+    }
+}
+
+pub fn cpal_err_callback(err: StreamError) {
+    eprintln!("an error occurred on stream: {}", err);
+}
@@ -0,0 +1,159 @@
+use jack::PortSpec;
+use std::sync::{Arc, Mutex};
+
+use crate::audio_processing::{AnalysisSettings, AudioProcessBuffer};
+use crate::input::{InputSettings, InputStatus};
+
+const CLIENT_NAME: &str = "ascii-audio-visualizer";
+
+pub type JackClient = jack::AsyncClient<Notifications, Listener>;
+
+// Registers `jack_ports` input ports and mixes them down to mono for analysis.
+// The returned client must be kept alive, dropping it deactivates and closes the client.
+pub fn connect(
+    settings: AnalysisSettings,
+    input: &InputSettings,
+) -> Result<(Arc<Mutex<AudioProcessBuffer>>, JackClient), anyhow::Error> {
+    let process_buffer_writer = Arc::new(Mutex::new(AudioProcessBuffer::new(settings)));
+    let process_buffer_reader = process_buffer_writer.clone();
+
+    let (client, _status) = jack::Client::new(CLIENT_NAME, jack::ClientOptions::NO_START_SERVER)
+        .map_err(|err| anyhow::Error::msg(format!("Failed to connect to JACK server: {err}")))?;
+
+    let ports = (0..input.jack_ports.max(1))
+        .map(|i| client.register_port(&format!("in_{}", i + 1), jack::AudioIn))
+        .collect::<Result<Vec<_>, _>>()?;
+    let port_names = ports
+        .iter()
+        .map(|port| port.name())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let sample_rate = client.sample_rate();
+    let listener = Listener {
+        ports,
+        mono: vec![0.0; client.buffer_size() as usize],
+        shared_buffer: process_buffer_writer.clone(),
+    };
+    let notifications = Notifications {
+        shared_buffer: process_buffer_writer.clone(),
+    };
+    let active_client = client.activate_async(notifications, listener)?;
+
+    // Optionally patch our ports to existing outputs, e.g. device = system:capture_
+    if let Some(pattern) = &input.device {
+        let sources = active_client.as_client().ports(
+            Some(pattern),
+            Some(jack::AudioIn.jack_port_type()),
+            jack::PortFlags::IS_OUTPUT,
+        );
+        if sources.is_empty() {
+            return Err(anyhow::Error::msg(format!(
+                "No JACK output ports match '{pattern}'"
+            )));
+        }
+        // Cycle through the sources so mono outputs feed every input
+        for (i, port_name) in port_names.iter().enumerate() {
+            let source = &sources[i % sources.len()];
+            active_client
+                .as_client()
+                .connect_ports_by_name(source, port_name)?;
+        }
+    }
+
+    if let Ok(mut buffer) = process_buffer_writer.lock() {
+        buffer.set_input_rate(sample_rate as u32);
+    }
+    set_status(
+        &process_buffer_writer,
+        InputStatus::Connected(format!(
+            "{CLIENT_NAME} ({} ports, {sample_rate}Hz)",
+            port_names.len()
+        )),
+    );
+    Ok((process_buffer_reader, active_client))
+}
+
+pub fn list_devices() -> Result<(), anyhow::Error> {
+    let (client, _status) = jack::Client::new(CLIENT_NAME, jack::ClientOptions::NO_START_SERVER)
+        .map_err(|err| anyhow::Error::msg(format!("Failed to connect to JACK server: {err}")))?;
+
+    println!("JACK output ports (use a name or regex as device):");
+    for port in client.ports(
+        None,
+        Some(jack::AudioIn.jack_port_type()),
+        jack::PortFlags::IS_OUTPUT,
+    ) {
+        println!("  {port}");
+    }
+    Ok(())
+}
+
+pub struct Listener {
+    ports: Vec<jack::Port<jack::AudioIn>>,
+    // Mono mix of the ports, sized to the server's buffer size
+    mono: Vec<f32>,
+    shared_buffer: Arc<Mutex<AudioProcessBuffer>>,
+}
+
+impl jack::ProcessHandler for Listener {
+    fn process(&mut self, _: &jack::Client, process_scope: &jack::ProcessScope) -> jack::Control {
+        let n_frames = process_scope.n_frames() as usize;
+        if self.mono.len() < n_frames {
+            // buffer_size is always called first, this only guards against a misbehaving server
+            return jack::Control::Continue;
+        }
+
+        let mono = &mut self.mono[..n_frames];
+        mono.fill(0.0);
+        for port in self.ports.iter() {
+            for (mixed, sample) in mono.iter_mut().zip(port.as_slice(process_scope)) {
+                *mixed += *sample;
+            }
+        }
+
+        // never block the realtime thread, drop the period if the UI holds the lock
+        if let Ok(mut buffer) = self.shared_buffer.try_lock() {
+            let scale = 1.0 / (self.ports.len() as f32);
+            for sample in mono.iter() {
+                buffer.push(sample * scale);
+            }
+        }
+        jack::Control::Continue
+    }
+
+    fn buffer_size(&mut self, _: &jack::Client, size: jack::Frames) -> jack::Control {
+        // called outside the process cycle, so allocating here is fine
+        self.mono.resize(size as usize, 0.0);
+        jack::Control::Continue
+    }
+}
+
+pub struct Notifications {
+    shared_buffer: Arc<Mutex<AudioProcessBuffer>>,
+}
+
+impl jack::NotificationHandler for Notifications {
+    fn shutdown(&mut self, _status: jack::ClientStatus, reason: &str) {
+        set_status(
+            &self.shared_buffer,
+            InputStatus::Disconnected(format!("JACK server shut down: {reason}")),
+        );
+    }
+
+    fn sample_rate(&mut self, _: &jack::Client, sample_rate: jack::Frames) -> jack::Control {
+        if let Ok(mut buffer) = self.shared_buffer.lock() {
+            buffer.set_input_rate(sample_rate);
+        }
+        set_status(
+            &self.shared_buffer,
+            InputStatus::Connected(format!("{CLIENT_NAME} ({sample_rate}Hz)")),
+        );
+        jack::Control::Continue
+    }
+}
+
+fn set_status(shared_buffer: &Arc<Mutex<AudioProcessBuffer>>, status: InputStatus) {
+    if let Ok(mut buffer) = shared_buffer.lock() {
+        buffer.status = status;
+    }
+}
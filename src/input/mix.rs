@@ -0,0 +1,65 @@
+// Sums several sources into one stream that is analysed like any other input.
+// Sources run on their own clocks, so samples are queued per source and mixed once
+// every source has delivered; a source that falls behind by more than MAX_LAG is
+// treated as silent so a disconnected device doesn't stall the mix.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::audio_processing::{AnalysisSettings, AudioProcessBuffer, FS};
+use crate::input::InputStatus;
+
+const MAX_LAG: usize = FS / 5;
+
+pub struct MixBus {
+    queues: Vec<VecDeque<f32>>,
+    output: Arc<Mutex<AudioProcessBuffer>>,
+    // Mixed samples waiting for the output lock, reused between pushes
+    mixed: Vec<f32>,
+}
+
+impl MixBus {
+    pub fn new(settings: AnalysisSettings, sources: usize) -> MixBus {
+        let mut output = AudioProcessBuffer::new(settings);
+        output.status = InputStatus::Connected(String::from("mix"));
+        MixBus {
+            queues: vec![VecDeque::new(); sources],
+            output: Arc::new(Mutex::new(output)),
+            mixed: vec![],
+        }
+    }
+
+    pub fn output(&self) -> Arc<Mutex<AudioProcessBuffer>> {
+        self.output.clone()
+    }
+
+    // Samples at the analysis rate from source number `source`
+    pub fn push(&mut self, source: usize, samples: &[f32]) {
+        self.queues[source].extend(samples);
+
+        let scale = 1.0 / (self.queues.len() as f32);
+        loop {
+            let ready = self.queues.iter().all(|queue| !queue.is_empty());
+            let overdue = self.queues.iter().any(|queue| queue.len() > MAX_LAG);
+            if !ready && !overdue {
+                break;
+            }
+            let sum: f32 = self
+                .queues
+                .iter_mut()
+                .map(|queue| queue.pop_front().unwrap_or(0.0))
+                .sum();
+            self.mixed.push(sum * scale);
+        }
+
+        if self.mixed.is_empty() {
+            return;
+        }
+        if let Ok(mut output) = self.output.lock() {
+            for sample in self.mixed.iter() {
+                output.push(*sample);
+            }
+        }
+        self.mixed.clear();
+    }
+}
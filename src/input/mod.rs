@@ -1,3 +1,9 @@
+use std::any::Any;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use crate::audio_processing::{AnalysisSettings, AudioProcessBuffer};
+
 // Compile with pulse bindings on linux, to listen via pulseaudio
 #[cfg(any(
     target_os = "linux",
@@ -7,6 +13,282 @@
 ))]
 pub mod pulse;
 
-// Compile with CPAL on windows, to listen via WASAPI
-#[cfg(target_os = "windows")]
-pub mod wasapi;
+// Native PipeWire capture, needs the 'pipewire' cargo feature
+#[cfg(all(unix, feature = "pipewire"))]
+pub mod pipewire;
+
+// JACK client with patchable input ports, needs the 'jack' cargo feature
+#[cfg(feature = "jack")]
+pub mod jack;
+
+// CPAL hosts: WASAPI on windows (with loopback), ALSA (e.g. snd-aloop) or JACK on linux
+pub mod cpal;
+
+// Raw PCM or RTP streams received over UDP, e.g. from module-rtp-send or ffmpeg
+pub mod network;
+
+// Sum of several inputs, analysed as a source of its own
+pub mod mix;
+
+use mix::MixBus;
+use network::NetworkSettings;
+
+// Audio system to capture from
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Backend {
+    Pulse,
+    PipeWire,
+    Jack,
+    Cpal,
+    Network,
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        if cfg!(target_os = "windows") {
+            Backend::Cpal
+        } else {
+            Backend::Pulse
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pulse" | "pulseaudio" => Ok(Backend::Pulse),
+            "pipewire" => Ok(Backend::PipeWire),
+            "jack" => Ok(Backend::Jack),
+            "cpal" | "alsa" | "wasapi" => Ok(Backend::Cpal),
+            "network" | "udp" | "rtp" => Ok(Backend::Network),
+            _ => Err(format!(
+                "Unknown input backend '{s}' (expected 'pulse', 'pipewire', 'jack', 'cpal' or 'network')"
+            )),
+        }
+    }
+}
+
+// What to capture when no device is named explicitly
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum CaptureMode {
+    // Whatever is playing on the default output device
+    #[default]
+    Monitor,
+    // The default input device, e.g. a microphone
+    Microphone,
+}
+
+impl FromStr for CaptureMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "monitor" => Ok(CaptureMode::Monitor),
+            "microphone" | "mic" => Ok(CaptureMode::Microphone),
+            _ => Err(format!(
+                "Unknown capture mode '{s}' (expected 'monitor' or 'microphone')"
+            )),
+        }
+    }
+}
+
+// Connection state of the capture backend, shown by the UI while not connected
+#[derive(Clone, PartialEq, Debug)]
+pub enum InputStatus {
+    Connecting,
+    // Name of the device being captured
+    Connected(String),
+    // Why the backend lost its device, it keeps retrying in the background
+    Disconnected(String),
+}
+
+#[derive(Clone)]
+pub struct InputSettings {
+    pub backend: Backend,
+    // cpal host to use, e.g. 'ALSA' or 'WASAPI', the platform default when None
+    pub host: Option<String>,
+    // Source or sink to capture, the default sink monitor / default source when None
+    pub device: Option<String>,
+    pub capture: CaptureMode,
+    // Number of input ports registered by the JACK backend
+    pub jack_ports: usize,
+    // Listen address and stream format of the network backend
+    pub network: NetworkSettings,
+}
+
+impl Default for InputSettings {
+    fn default() -> Self {
+        InputSettings {
+            backend: Backend::default(),
+            host: None,
+            device: None,
+            capture: CaptureMode::default(),
+            jack_ports: 2,
+            network: NetworkSettings::default(),
+        }
+    }
+}
+
+// A connected input, analysed features are published through `buffer`
+pub struct InputHandle {
+    pub buffer: Arc<Mutex<AudioProcessBuffer>>,
+    // Backend resources that must outlive the capture, e.g. a cpal stream
+    _keep_alive: Option<Box<dyn Any>>,
+}
+
+impl InputHandle {
+    pub fn new(buffer: Arc<Mutex<AudioProcessBuffer>>) -> InputHandle {
+        InputHandle {
+            buffer,
+            _keep_alive: None,
+        }
+    }
+}
+
+pub fn connect(
+    settings: AnalysisSettings,
+    input: &InputSettings,
+) -> Result<InputHandle, anyhow::Error> {
+    match input.backend {
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd"
+        ))]
+        Backend::Pulse => Ok(InputHandle::new(pulse::connect(settings, input)?)),
+
+        #[cfg(all(unix, feature = "pipewire"))]
+        Backend::PipeWire => Ok(InputHandle::new(pipewire::connect(settings, input)?)),
+
+        #[cfg(feature = "jack")]
+        Backend::Jack => {
+            let (buffer, client) = jack::connect(settings, input)?;
+            Ok(InputHandle {
+                buffer,
+                _keep_alive: Some(Box::new(client)),
+            })
+        }
+
+        Backend::Cpal => {
+            let (buffer, stream) = cpal::connect(settings, input)?;
+            Ok(InputHandle {
+                buffer,
+                _keep_alive: Some(Box::new(stream)),
+            })
+        }
+
+        Backend::Network => Ok(InputHandle::new(network::connect(settings, input)?)),
+
+        #[allow(unreachable_patterns)]
+        backend => Err(unavailable(backend)),
+    }
+}
+
+// Every configured input by name, plus their mix when there are several
+pub struct Sources {
+    pub inputs: Vec<(String, InputHandle)>,
+    pub mix: Option<Arc<Mutex<AudioProcessBuffer>>>,
+}
+
+impl Sources {
+    pub fn connect(
+        settings: AnalysisSettings,
+        inputs: &[(String, InputSettings)],
+    ) -> Result<Sources, anyhow::Error> {
+        let mix_bus = match inputs.len() {
+            0 | 1 => None,
+            count => Some(Arc::new(Mutex::new(MixBus::new(settings.clone(), count)))),
+        };
+        let mut connected = vec![];
+        for (index, (name, input)) in inputs.iter().enumerate() {
+            let handle = connect(settings.clone(), input)
+                .map_err(|err| anyhow::Error::msg(format!("Source '{name}': {err}")))?;
+            if let (Some(mix_bus), Ok(mut buffer)) = (&mix_bus, handle.buffer.lock()) {
+                buffer.send_to_mix(mix_bus.clone(), index);
+            }
+            connected.push((name.clone(), handle));
+        }
+        let mix = mix_bus
+            .as_ref()
+            .and_then(|mix_bus| Some(mix_bus.lock().ok()?.output()));
+        Ok(Sources {
+            inputs: connected,
+            mix,
+        })
+    }
+
+    pub fn single(name: &str, handle: InputHandle) -> Sources {
+        Sources {
+            inputs: vec![(name.to_string(), handle)],
+            mix: None,
+        }
+    }
+
+    // The named input or the mix, the default (mix, else the first input) for None or unknown names
+    pub fn buffer(&self, name: Option<&str>) -> &Arc<Mutex<AudioProcessBuffer>> {
+        let named = self
+            .inputs
+            .iter()
+            .find(|(input_name, _handle)| Some(input_name.as_str()) == name)
+            .map(|(_name, handle)| &handle.buffer);
+        named
+            .or(self.mix.as_ref())
+            .unwrap_or(&self.inputs[0].1.buffer)
+    }
+
+    // The mix reports the first input that isn't connected
+    pub fn status(&self, name: Option<&str>) -> Option<InputStatus> {
+        let buffer = self.buffer(name);
+        if self
+            .mix
+            .as_ref()
+            .is_some_and(|mix| Arc::ptr_eq(mix, buffer))
+        {
+            for (_name, handle) in self.inputs.iter() {
+                let status = handle.buffer.try_lock().ok()?.status.clone();
+                if !matches!(status, InputStatus::Connected(_)) {
+                    return Some(status);
+                }
+            }
+        }
+        Some(buffer.try_lock().ok()?.status.clone())
+    }
+}
+
+fn unavailable(backend: Backend) -> anyhow::Error {
+    let hint = match backend {
+        Backend::PipeWire => " (build with '--features pipewire')",
+        Backend::Jack => " (build with '--features jack')",
+        _ => "",
+    };
+    anyhow::Error::msg(format!(
+        "Input backend {backend:?} is not available in this build{hint}"
+    ))
+}
+
+// Prints the devices the active backend can capture from
+pub fn list_devices(input: &InputSettings) -> Result<(), anyhow::Error> {
+    match input.backend {
+        // PipeWire nodes are listed through its pulse compatibility layer
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd"
+        ))]
+        Backend::Pulse | Backend::PipeWire => pulse::list_devices(),
+
+        #[cfg(feature = "jack")]
+        Backend::Jack => jack::list_devices(),
+
+        Backend::Cpal => cpal::list_devices(input),
+
+        Backend::Network => network::list_devices(input),
+
+        #[allow(unreachable_patterns)]
+        backend => Err(unavailable(backend)),
+    }
+}
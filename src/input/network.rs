@@ -0,0 +1,489 @@
+// Receives PCM over UDP, either as bare datagrams or as RTP (L16, as sent by
+// PulseAudio's module-rtp-send or `ffmpeg -f rtp`). RTP packets are put back in
+// order by a small jitter buffer, lost packets are replaced with silence.
+
+use std::collections::BTreeMap;
+use std::net::UdpSocket;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::audio_formats::{SampleDecoder, SampleFormat};
+use crate::audio_processing::{AnalysisSettings, AudioProcessBuffer};
+use crate::input::{InputSettings, InputStatus};
+
+const MAX_DATAGRAM_SIZE: usize = 65_536;
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+// Report the stream as gone after this long without packets
+const STALL_TIMEOUT: Duration = Duration::from_secs(2);
+const RTP_VERSION: u8 = 2;
+const RTP_HEADER_SIZE: usize = 12;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Protocol {
+    // Each datagram is a chunk of interleaved samples, played in arrival order
+    Raw,
+    Rtp,
+}
+
+impl FromStr for Protocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" | "udp" => Ok(Protocol::Raw),
+            "rtp" => Ok(Protocol::Rtp),
+            _ => Err(format!(
+                "Unknown network protocol '{s}' (expected 'raw' or 'rtp')"
+            )),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct NetworkSettings {
+    pub listen: String,
+    pub protocol: Protocol,
+    pub format: SampleFormat,
+    pub rate: u32,
+    pub channels: usize,
+    // How much audio the jitter buffer holds back to reorder late packets
+    pub jitter_ms: u32,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        // module-rtp-send defaults
+        NetworkSettings {
+            listen: String::from("0.0.0.0:46000"),
+            protocol: Protocol::Rtp,
+            format: SampleFormat::S16BE,
+            rate: 44100,
+            channels: 2,
+            jitter_ms: 40,
+        }
+    }
+}
+
+pub fn connect(
+    settings: AnalysisSettings,
+    input: &InputSettings,
+) -> Result<Arc<Mutex<AudioProcessBuffer>>, anyhow::Error> {
+    let network = input.network.clone();
+    let socket = UdpSocket::bind(&network.listen).map_err(|err| {
+        anyhow::Error::msg(format!("Failed to listen on '{}': {err}", network.listen))
+    })?;
+    socket.set_read_timeout(Some(READ_TIMEOUT))?;
+
+    let process_buffer_writer = Arc::new(Mutex::new(AudioProcessBuffer::new(settings)));
+    if let Ok(mut buffer) = process_buffer_writer.lock() {
+        buffer.set_input_rate(network.rate);
+    }
+    let process_buffer_reader = process_buffer_writer.clone();
+    thread::spawn(move || {
+        audio_listener(process_buffer_writer, socket, network);
+    });
+
+    Ok(process_buffer_reader)
+}
+
+// There is nothing to enumerate, show what the backend would listen for instead
+pub fn list_devices(input: &InputSettings) -> Result<(), anyhow::Error> {
+    let network = &input.network;
+    println!(
+        "Listening for {:?} packets on {} ({:?}, {}Hz, {} channels)",
+        network.protocol, network.listen, network.format, network.rate, network.channels
+    );
+    println!("Send audio with e.g.:");
+    println!(
+        "  pactl load-module module-rtp-send source=<sink>.monitor destination_ip=<host> port=<port>"
+    );
+    println!("  ffmpeg -re -i <file> -ac 2 -ar 44100 -acodec pcm_s16be -f rtp rtp://<host>:<port>");
+    Ok(())
+}
+
+fn audio_listener(
+    shared_buffer: Arc<Mutex<AudioProcessBuffer>>,
+    socket: UdpSocket,
+    network: NetworkSettings,
+) {
+    let mut datagram = vec![0; MAX_DATAGRAM_SIZE];
+    // Raw streams may split frames across datagrams, RTP payloads are whole frames
+    let mut decoder = SampleDecoder::new(network.format, network.channels);
+    let mut samples = vec![];
+    let mut jitter_buffer = JitterBuffer::new(network.jitter_ms * network.rate / 1000);
+    let mut last_packet: Option<Instant> = None;
+    let mut connected = false;
+    set_status(&shared_buffer, InputStatus::Connecting);
+
+    loop {
+        let received = match socket.recv_from(&mut datagram) {
+            Ok((len, _sender)) => Some(&datagram[..len]),
+            Err(_) => None,
+        };
+        let timed_out = received.is_none();
+
+        match received {
+            Some(packet) => {
+                last_packet = Some(Instant::now());
+                if !connected {
+                    connected = true;
+                    set_status(
+                        &shared_buffer,
+                        InputStatus::Connected(format!(
+                            "{:?} on {} ({}Hz, {} channels)",
+                            network.protocol, network.listen, network.rate, network.channels
+                        )),
+                    );
+                }
+                match network.protocol {
+                    Protocol::Raw => {
+                        decoder.decode_into(packet, &mut samples);
+                        push_samples(&shared_buffer, &downmix(&samples, network.channels));
+                    }
+                    Protocol::Rtp => {
+                        let Some((sequence, payload)) = parse_rtp(packet) else {
+                            continue;
+                        };
+                        let samples = downmix(&network.format.decode(payload), network.channels);
+                        jitter_buffer.insert(sequence, samples);
+                    }
+                }
+            }
+            None => {
+                // Nothing arrived in time, whatever is still held back gets played out below
+                if connected && last_packet.is_some_and(|last| last.elapsed() > STALL_TIMEOUT) {
+                    connected = false;
+                    set_status(
+                        &shared_buffer,
+                        InputStatus::Disconnected(format!(
+                            "no packets on {} for {}s",
+                            network.listen,
+                            STALL_TIMEOUT.as_secs()
+                        )),
+                    );
+                }
+            }
+        }
+
+        while let Some(samples) = jitter_buffer.pop(timed_out) {
+            push_samples(&shared_buffer, &samples);
+        }
+        if timed_out {
+            // the sender may restart with a new random sequence number
+            jitter_buffer.reset();
+        }
+    }
+}
+
+fn push_samples(shared_buffer: &Arc<Mutex<AudioProcessBuffer>>, samples: &[f32]) {
+    if let Ok(mut buffer) = shared_buffer.try_lock() {
+        for sample in samples {
+            buffer.push(*sample);
+        }
+    }
+}
+
+fn set_status(shared_buffer: &Arc<Mutex<AudioProcessBuffer>>, status: InputStatus) {
+    if let Ok(mut buffer) = shared_buffer.lock() {
+        buffer.status = status;
+    }
+}
+
+fn downmix(samples: &[f32], channels: usize) -> Vec<f32> {
+    let channels = channels.max(1);
+    samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / (channels as f32))
+        .collect()
+}
+
+// Returns the sequence number and payload of an RTP packet, None if it isn't valid RTP
+fn parse_rtp(packet: &[u8]) -> Option<(u16, &[u8])> {
+    if packet.len() < RTP_HEADER_SIZE || packet[0] >> 6 != RTP_VERSION {
+        return None;
+    }
+    let has_padding = packet[0] & 0x20 != 0;
+    let has_extension = packet[0] & 0x10 != 0;
+    let csrc_count = (packet[0] & 0x0f) as usize;
+    let sequence = u16::from_be_bytes([packet[2], packet[3]]);
+
+    let mut start = RTP_HEADER_SIZE + 4 * csrc_count;
+    if has_extension {
+        let header = packet.get(start..start + 4)?;
+        let words = u16::from_be_bytes([header[2], header[3]]) as usize;
+        start += 4 + 4 * words;
+    }
+    let mut end = packet.len();
+    if has_padding {
+        end = end.checked_sub(*packet.last()? as usize)?;
+    }
+    Some((sequence, packet.get(start..end)?))
+}
+
+// Holds packets back until `target_samples` of audio are queued, then plays them out
+// in sequence order. A missing packet is skipped (as silence) once enough later audio
+// has arrived that it is unlikely to still show up. A gap longer than the buffer holds
+// isn't loss the buffer could have covered, e.g. the sender skipped ahead, so playback
+// resyncs to the next packet instead of playing out the gap as silence.
+struct JitterBuffer {
+    packets: BTreeMap<u64, Vec<f32>>,
+    queued_samples: usize,
+    target_samples: usize,
+    // next extended sequence number to play out
+    next_sequence: Option<u64>,
+    highest_sequence: u64,
+    // samples per packet, used for the length of silence that replaces a lost packet
+    packet_len: usize,
+}
+
+impl JitterBuffer {
+    fn new(target_samples: u32) -> JitterBuffer {
+        JitterBuffer {
+            packets: BTreeMap::new(),
+            queued_samples: 0,
+            target_samples: target_samples as usize,
+            next_sequence: None,
+            highest_sequence: 0,
+            packet_len: 0,
+        }
+    }
+
+    fn insert(&mut self, sequence: u16, samples: Vec<f32>) {
+        let sequence = self.extend_sequence(sequence);
+        if self.next_sequence.is_some_and(|next| sequence < next) {
+            // arrived after its slot was played out
+            return;
+        }
+        if self.next_sequence.is_none() {
+            self.next_sequence = Some(sequence);
+        }
+        self.highest_sequence = self.highest_sequence.max(sequence);
+        self.packet_len = samples.len();
+        self.queued_samples += samples.len();
+        if let Some(duplicate) = self.packets.insert(sequence, samples) {
+            self.queued_samples -= duplicate.len();
+        }
+    }
+
+    fn reset(&mut self) {
+        self.packets.clear();
+        self.queued_samples = 0;
+        self.next_sequence = None;
+        self.highest_sequence = 0;
+    }
+
+    // Widens a 16 bit sequence number using the highest one seen, so wrap-around keeps ordering
+    fn extend_sequence(&self, sequence: u16) -> u64 {
+        if self.next_sequence.is_none() {
+            // leave room below so early reordered packets don't underflow
+            return (1 << 16) + sequence as u64;
+        }
+        let highest = self.highest_sequence;
+        let candidate = (highest & !0xffff) | sequence as u64;
+        let half_range = 1 << 15;
+        if candidate + half_range < highest {
+            candidate + (1 << 16)
+        } else if candidate > highest + half_range && candidate >= 1 << 16 {
+            candidate - (1 << 16)
+        } else {
+            candidate
+        }
+    }
+
+    // Next packet in order, or silence for a lost one, once enough audio is buffered.
+    // When draining everything held back is played out, e.g. because the sender paused.
+    fn pop(&mut self, drain: bool) -> Option<Vec<f32>> {
+        if !drain && self.queued_samples <= self.target_samples {
+            return None;
+        }
+        let mut next = self.next_sequence?;
+        let first = *self.packets.keys().next()?;
+        let gap_samples = (first - next) as usize * self.packet_len;
+        if gap_samples > self.target_samples.max(self.packet_len) {
+            next = first;
+        }
+        self.next_sequence = Some(next + 1);
+        match self.packets.remove(&next) {
+            Some(samples) => {
+                self.queued_samples -= samples.len();
+                Some(samples)
+            }
+            None => Some(vec![0.0; self.packet_len]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A bare RTP header with `sequence`, followed by `payload`
+    fn rtp_packet(sequence: u16, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![RTP_VERSION << 6, 10];
+        packet.extend(sequence.to_be_bytes());
+        packet.extend([0; 8]);
+        packet.extend(payload);
+        packet
+    }
+
+    // Pops everything, `drain` as after a timeout
+    fn drain(jitter_buffer: &mut JitterBuffer) -> Vec<Vec<f32>> {
+        std::iter::from_fn(|| jitter_buffer.pop(true)).collect()
+    }
+
+    #[test]
+    fn parses_plain_rtp() {
+        let packet = rtp_packet(513, &[1, 2, 3, 4]);
+        assert_eq!(parse_rtp(&packet), Some((513, &[1, 2, 3, 4][..])));
+    }
+
+    #[test]
+    fn skips_csrcs_extension_and_padding() {
+        let mut packet = rtp_packet(7, &[]);
+        // 2 CSRCs, an extension and padding
+        packet[0] |= 0x20 | 0x10 | 2;
+        packet.extend([0xaa; 8]);
+        // extension header with one 32 bit word
+        packet.extend([0xbe, 0xde, 0, 1, 0xcc, 0xcc, 0xcc, 0xcc]);
+        packet.extend([5, 6, 7, 8]);
+        packet.extend([0, 0, 3]);
+        assert_eq!(parse_rtp(&packet), Some((7, &[5, 6, 7, 8][..])));
+    }
+
+    #[test]
+    fn rejects_invalid_rtp() {
+        assert_eq!(parse_rtp(&[0x80, 0, 0]), None);
+        let mut packet = rtp_packet(1, &[1, 2]);
+        packet[0] = 1 << 6;
+        assert_eq!(parse_rtp(&packet), None);
+        // extension header cut off
+        let mut packet = rtp_packet(1, &[0xbe]);
+        packet[0] |= 0x10;
+        assert_eq!(parse_rtp(&packet), None);
+        // more padding than payload
+        let mut packet = rtp_packet(1, &[200]);
+        packet[0] |= 0x20;
+        assert_eq!(parse_rtp(&packet), None);
+    }
+
+    #[test]
+    fn plays_reordered_packets_in_order() {
+        let mut jitter_buffer = JitterBuffer::new(0);
+        for sequence in [10, 12, 11, 13] {
+            jitter_buffer.insert(sequence, vec![sequence as f32]);
+        }
+        assert_eq!(
+            drain(&mut jitter_buffer),
+            [vec![10.0], vec![11.0], vec![12.0], vec![13.0]]
+        );
+    }
+
+    #[test]
+    fn holds_packets_back_until_target() {
+        let mut jitter_buffer = JitterBuffer::new(4);
+        jitter_buffer.insert(1, vec![0.1; 2]);
+        jitter_buffer.insert(2, vec![0.2; 2]);
+        assert_eq!(jitter_buffer.pop(false), None);
+        jitter_buffer.insert(3, vec![0.3; 2]);
+        assert_eq!(jitter_buffer.pop(false), Some(vec![0.1; 2]));
+        assert_eq!(jitter_buffer.pop(false), None);
+    }
+
+    #[test]
+    fn keeps_order_across_sequence_wraparound() {
+        let mut jitter_buffer = JitterBuffer::new(0);
+        for sequence in [65534, 0, 65535, 1] {
+            jitter_buffer.insert(sequence, vec![sequence as f32]);
+        }
+        assert_eq!(
+            drain(&mut jitter_buffer),
+            [vec![65534.0], vec![65535.0], vec![0.0], vec![1.0]]
+        );
+    }
+
+    #[test]
+    fn extends_sequence_numbers_monotonically() {
+        let mut jitter_buffer = JitterBuffer::new(1 << 20);
+        let mut extended = vec![];
+        for i in 0..200_000u32 {
+            let sequence = (i % 65536) as u16;
+            extended.push(jitter_buffer.extend_sequence(sequence));
+            jitter_buffer.insert(sequence, vec![]);
+        }
+        assert!(extended.windows(2).all(|pair| pair[1] == pair[0] + 1));
+    }
+
+    #[test]
+    fn replaces_lost_packet_with_silence() {
+        let mut jitter_buffer = JitterBuffer::new(8);
+        jitter_buffer.insert(10, vec![0.5; 4]);
+        jitter_buffer.insert(12, vec![0.5; 4]);
+        assert_eq!(
+            drain(&mut jitter_buffer),
+            [vec![0.5; 4], vec![0.0; 4], vec![0.5; 4]]
+        );
+    }
+
+    #[test]
+    fn drops_packets_that_arrive_too_late() {
+        let mut jitter_buffer = JitterBuffer::new(0);
+        jitter_buffer.insert(10, vec![1.0]);
+        jitter_buffer.insert(11, vec![2.0]);
+        assert_eq!(jitter_buffer.pop(false), Some(vec![1.0]));
+        jitter_buffer.insert(9, vec![3.0]);
+        assert_eq!(drain(&mut jitter_buffer), [vec![2.0]]);
+    }
+
+    #[test]
+    fn resyncs_on_a_jump_longer_than_the_buffer() {
+        let mut jitter_buffer = JitterBuffer::new(16);
+        jitter_buffer.insert(10, vec![0.5; 4]);
+        jitter_buffer.insert(5000, vec![0.25; 4]);
+        // no seconds of silence for the skipped sequence numbers
+        assert_eq!(drain(&mut jitter_buffer), [vec![0.5; 4], vec![0.25; 4]]);
+    }
+
+    #[test]
+    fn receives_rtp_over_udp_loopback() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_read_timeout(Some(READ_TIMEOUT)).unwrap();
+        let address = receiver.local_addr().unwrap();
+        let network = NetworkSettings {
+            listen: address.to_string(),
+            rate: 48000,
+            jitter_ms: 5,
+            ..NetworkSettings::default()
+        };
+        let shared_buffer = Arc::new(Mutex::new(AudioProcessBuffer::default()));
+        let writer = shared_buffer.clone();
+        thread::spawn(move || audio_listener(writer, receiver, network));
+
+        // 20 packets of 240 stereo frames at half scale, sent out of order
+        let payload = 0x4000i16.to_be_bytes().repeat(480);
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        for sequence in (0..20u16).map(|i| i ^ 1) {
+            sender
+                .send_to(&rtp_packet(sequence, &payload), address)
+                .unwrap();
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            // the level only moves once a full buffer of received audio has been analyzed
+            let (status, level) = {
+                let buffer = shared_buffer.lock().unwrap();
+                (
+                    buffer.status.clone(),
+                    buffer.features.root_mean_squared.smoothed_val,
+                )
+            };
+            if matches!(status, InputStatus::Connected(_)) && level > 0.0 {
+                break;
+            }
+            assert!(Instant::now() < deadline, "nothing received, {status:?}");
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
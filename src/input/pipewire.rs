@@ -0,0 +1,190 @@
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use pipewire as pw;
+use pw::{properties::properties, spa};
+use spa::param::format::{MediaSubtype, MediaType};
+use spa::param::format_utils;
+use spa::pod::Pod;
+
+use crate::audio_formats::SampleFormat;
+use crate::audio_processing::{AnalysisSettings, AudioProcessBuffer, FS};
+use crate::input::{CaptureMode, InputSettings, InputStatus};
+
+struct UserData {
+    format: spa::param::audio::AudioInfoRaw,
+    shared_buffer: Arc<Mutex<AudioProcessBuffer>>,
+    target: String,
+    // Decoded interleaved samples, reused between process calls
+    samples: Vec<f32>,
+}
+
+pub fn connect(
+    settings: AnalysisSettings,
+    input: &InputSettings,
+) -> Result<Arc<Mutex<AudioProcessBuffer>>, anyhow::Error> {
+    let process_buffer_writer = Arc::new(Mutex::new(AudioProcessBuffer::new(settings)));
+    let process_buffer_reader = process_buffer_writer.clone();
+
+    // PipeWire objects aren't Send, so everything lives on the listener thread.
+    // Setup errors are sent back so they can be reported before the UI starts.
+    let (setup_sender, setup_receiver) = mpsc::channel();
+    let input = input.clone();
+    thread::spawn(move || {
+        if let Err(err) = audio_listener(process_buffer_writer, &input, &setup_sender) {
+            let _ = setup_sender.send(Err(err));
+        }
+    });
+
+    setup_receiver
+        .recv()
+        .map_err(|_| anyhow::Error::msg("PipeWire listener exited during setup"))??;
+    Ok(process_buffer_reader)
+}
+
+fn audio_listener(
+    shared_buffer: Arc<Mutex<AudioProcessBuffer>>,
+    input: &InputSettings,
+    setup_sender: &mpsc::Sender<Result<(), anyhow::Error>>,
+) -> Result<(), anyhow::Error> {
+    pw::init();
+
+    let mainloop = pw::main_loop::MainLoop::new(None)?;
+    let context = pw::context::Context::new(&mainloop)?;
+    let core = context.connect(None)?;
+
+    let mut props = properties! {
+        *pw::keys::MEDIA_TYPE => "Audio",
+        *pw::keys::MEDIA_CATEGORY => "Capture",
+        *pw::keys::MEDIA_ROLE => "Music",
+        *pw::keys::APP_NAME => "Audio Listener",
+        *pw::keys::NODE_NAME => "ascii-audio-visualizer",
+    };
+    // A node name or object.serial, a sink is recorded through its monitor ports
+    if let Some(device) = &input.device {
+        props.insert(*pw::keys::TARGET_OBJECT, device.as_str());
+    }
+    if input.capture == CaptureMode::Monitor {
+        props.insert(*pw::keys::STREAM_CAPTURE_SINK, "true");
+    }
+
+    let stream = pw::stream::Stream::new(&core, "listener", props)?;
+
+    let data = UserData {
+        format: Default::default(),
+        shared_buffer: shared_buffer.clone(),
+        target: input.device.clone().unwrap_or(String::from("default")),
+        samples: vec![],
+    };
+
+    let _listener = stream
+        .add_local_listener_with_user_data(data)
+        .state_changed(|_, user_data, _old, new| {
+            let status = match new {
+                pw::stream::StreamState::Streaming => InputStatus::Connected(format!(
+                    "{} ({}Hz, {} channels)",
+                    user_data.target,
+                    user_data.format.rate(),
+                    user_data.format.channels()
+                )),
+                pw::stream::StreamState::Error(err) => InputStatus::Disconnected(err),
+                pw::stream::StreamState::Unconnected => {
+                    InputStatus::Disconnected(String::from("stream unconnected"))
+                }
+                pw::stream::StreamState::Connecting | pw::stream::StreamState::Paused => {
+                    InputStatus::Connecting
+                }
+            };
+            if let Ok(mut buffer) = user_data.shared_buffer.lock() {
+                buffer.status = status;
+            }
+        })
+        .param_changed(|_, user_data, id, param| {
+            // None means the format was cleared
+            let Some(param) = param else {
+                return;
+            };
+            if id != pw::spa::param::ParamType::Format.as_raw() {
+                return;
+            }
+
+            let Ok((media_type, media_subtype)) = format_utils::parse_format(param) else {
+                return;
+            };
+            // only accept raw audio
+            if media_type != MediaType::Audio || media_subtype != MediaSubtype::Raw {
+                return;
+            }
+
+            // store the negotiated rate and channel count
+            if user_data.format.parse(param).is_ok() {
+                if let Ok(mut buffer) = user_data.shared_buffer.lock() {
+                    buffer.set_input_rate(user_data.format.rate());
+                }
+            }
+        })
+        .process(|stream, user_data| {
+            let Some(mut buffer) = stream.dequeue_buffer() else {
+                return;
+            };
+            let datas = buffer.datas_mut();
+            if datas.is_empty() {
+                return;
+            }
+
+            let data = &mut datas[0];
+            let n_channels = user_data.format.channels().max(1) as usize;
+            let n_bytes = data.chunk().size() as usize;
+            let Some(samples) = data.data() else {
+                return;
+            };
+
+            // downmix interleaved f32 frames to mono
+            user_data.samples.clear();
+            SampleFormat::F32LE.decode_into(
+                &samples[..n_bytes.min(samples.len())],
+                &mut user_data.samples,
+            );
+            if let Ok(mut process_buffer) = user_data.shared_buffer.try_lock() {
+                for frame in user_data.samples.chunks_exact(n_channels) {
+                    process_buffer.push(frame.iter().sum::<f32>() / (n_channels as f32));
+                }
+            }
+        })
+        .register()?;
+
+    // Ask for f32 at the analysis rate, PipeWire converts whatever the graph runs at.
+    // Channels are left open so we get the node's native layout.
+    let mut audio_info = spa::param::audio::AudioInfoRaw::new();
+    audio_info.set_format(spa::param::audio::AudioFormat::F32LE);
+    audio_info.set_rate(FS as u32);
+    let format = pw::spa::pod::Object {
+        type_: pw::spa::utils::SpaTypes::ObjectParamFormat.as_raw(),
+        id: pw::spa::param::ParamType::EnumFormat.as_raw(),
+        properties: audio_info.into(),
+    };
+    let values: Vec<u8> = pw::spa::pod::serialize::PodSerializer::serialize(
+        std::io::Cursor::new(Vec::new()),
+        &pw::spa::pod::Value::Object(format),
+    )
+    .map_err(|err| anyhow::Error::msg(format!("Failed to build PipeWire format: {err:?}")))?
+    .0
+    .into_inner();
+    let mut params = [
+        Pod::from_bytes(&values).ok_or(anyhow::Error::msg("Failed to build PipeWire format"))?
+    ];
+
+    stream.connect(
+        spa::utils::Direction::Input,
+        None,
+        pw::stream::StreamFlags::AUTOCONNECT
+            | pw::stream::StreamFlags::MAP_BUFFERS
+            | pw::stream::StreamFlags::RT_PROCESS,
+        &mut params,
+    )?;
+
+    let _ = setup_sender.send(Ok(()));
+    mainloop.run();
+    Ok(())
+}
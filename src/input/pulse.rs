@@ -1,39 +1,162 @@
+use std::cell::Cell;
 use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
 
 use psimple::Simple;
+use pulse::callbacks::ListResult;
+use pulse::context::subscribe::{Facility, InterestMaskSet};
 use pulse::context::{Context, FlagSet as ContextFlagSet};
 use pulse::def::{BufferAttr, Retval};
 use pulse::mainloop::standard::Mainloop;
+use pulse::operation::{Operation, State as OperationState};
 use pulse::sample::{Format, Spec};
 use pulse::stream::Direction;
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use crate::audio_formats::AsF32Audio;
-use crate::audio_processing::AudioProcessBuffer;
+use crate::audio_formats::{SampleDecoder, SampleFormat};
+use crate::audio_processing::{AnalysisSettings, AudioProcessBuffer};
+use crate::input::{CaptureMode, InputSettings, InputStatus};
 
 const BUFFER_SIZE: usize = 1024;
+const SAMPLE_RATE: u32 = 44100;
+const MIN_BACKOFF: Duration = Duration::from_millis(250);
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+// How often the recorder looks for a new default device while the stream delivers nothing
+const GENERATION_POLL: Duration = Duration::from_millis(100);
 
-pub fn connect() -> Result<Arc<Mutex<AudioProcessBuffer>>, anyhow::Error> {
-    let name = get_default_sink_name();
-    println!("{}", name);
+// Samples from one read of the stream, or why reading stopped
+type Chunk = Result<Vec<f32>, String>;
 
-    let process_buffer_writer = Arc::new(Mutex::new(AudioProcessBuffer::new()));
+// The receiving end of a reader thread. Sample buffers go back through `spare` once
+// they are pushed, so reading settles on a couple of buffers instead of allocating.
+struct Reader {
+    chunks: Receiver<Chunk>,
+    spare: Sender<Vec<f32>>,
+}
+
+pub fn connect(
+    settings: AnalysisSettings,
+    input: &InputSettings,
+) -> Result<Arc<Mutex<AudioProcessBuffer>>, anyhow::Error> {
+    // Fail early on an unknown device, later failures are retried by the listener
+    let source_name = resolve_source(input)?;
+
+    let process_buffer_writer = Arc::new(Mutex::new(AudioProcessBuffer::new(settings)));
     let process_buffer_reader = process_buffer_writer.clone();
+
+    // Only follow the server's defaults when no device was asked for
+    let defaults_generation = Arc::new(AtomicUsize::new(0));
+    if input.device.is_none() {
+        let generation_writer = defaults_generation.clone();
+        thread::spawn(move || {
+            default_device_watcher(generation_writer);
+        });
+    }
+
+    let input = input.clone();
     thread::spawn(move || {
-        audio_listener(process_buffer_writer);
+        audio_listener(
+            process_buffer_writer,
+            input,
+            source_name,
+            defaults_generation,
+        );
     });
 
     Ok(process_buffer_reader)
 }
 
-fn audio_listener(shared_buffer: Arc<Mutex<AudioProcessBuffer>>) {
+fn audio_listener(
+    shared_buffer: Arc<Mutex<AudioProcessBuffer>>,
+    input: InputSettings,
+    source_name: String,
+    defaults_generation: Arc<AtomicUsize>,
+) {
+    let mut source_name = Some(source_name);
+    let mut backoff = Backoff::new();
+    loop {
+        let wait = session(&shared_buffer, &defaults_generation, &mut backoff, || {
+            let source_name = source_name
+                .take()
+                .map_or_else(|| resolve_source(&input), Ok)?;
+            let stream = open_stream(&source_name)?;
+            Ok((source_name, spawn_reader(stream)))
+        });
+        if let Some(wait) = wait {
+            thread::sleep(wait);
+        }
+    }
+}
+
+// Waits between reconnects, doubling from MIN_BACKOFF up to MAX_BACKOFF
+struct Backoff {
+    next: Duration,
+}
+
+impl Backoff {
+    fn new() -> Backoff {
+        Backoff { next: MIN_BACKOFF }
+    }
+
+    fn reset(&mut self) {
+        self.next = MIN_BACKOFF;
+    }
+
+    // How long to wait before the next attempt
+    fn wait(&mut self) -> Duration {
+        let wait = self.next;
+        self.next = (self.next * 2).min(MAX_BACKOFF);
+        wait
+    }
+}
+
+// One connection: opens a stream with `open` and records from it until the default device
+// changes or the stream fails. Returns how long to wait before the next attempt, None to
+// reopen straight away.
+fn session(
+    shared_buffer: &Arc<Mutex<AudioProcessBuffer>>,
+    defaults_generation: &AtomicUsize,
+    backoff: &mut Backoff,
+    open: impl FnOnce() -> Result<(String, Reader), anyhow::Error>,
+) -> Option<Duration> {
+    let generation = defaults_generation.load(Ordering::Relaxed);
+    let result = open().and_then(|(source_name, reader)| {
+        if let Ok(mut buffer) = shared_buffer.lock() {
+            buffer.set_input_rate(SAMPLE_RATE);
+        }
+        set_status(shared_buffer, InputStatus::Connected(source_name));
+        backoff.reset();
+        record(&reader, shared_buffer, defaults_generation, generation)
+    });
+
+    match result {
+        // Default device changed, reopen straight away
+        Ok(()) => {
+            set_status(shared_buffer, InputStatus::Connecting);
+            None
+        }
+        // Server went away or the device vanished, retry with backoff
+        Err(err) => {
+            let wait = backoff.wait();
+            set_status(
+                shared_buffer,
+                InputStatus::Disconnected(format!("{err}, retrying in {:.1}s", wait.as_secs_f32())),
+            );
+            Some(wait)
+        }
+    }
+}
+
+fn open_stream(source_name: &str) -> Result<Simple, anyhow::Error> {
     let spec = Spec {
-        format: Format::FLOAT32NE,
+        format: Format::F32le,
         channels: 1,
-        rate: 44100,
+        rate: SAMPLE_RATE,
     };
     assert!(spec.is_valid());
 
@@ -45,95 +168,463 @@ fn audio_listener(shared_buffer: Arc<Mutex<AudioProcessBuffer>>) {
         fragsize: BUFFER_SIZE as u32,
     };
 
-    let mut default_sink_monitor = get_default_sink_name();
-    default_sink_monitor.push_str(".monitor");
-
-    let s = Simple::new(
+    Simple::new(
         None,              // Use the default server
         "Audio Listener",  // Our application’s name
-        Direction::Record, // We want a playback stream
-        Some(default_sink_monitor.as_str()),
+        Direction::Record, // We want a record stream
+        Some(source_name),
         "listener", // Description of our stream
         &spec,      // Our sample format
         None,       // Use default channel map
         Some(&attributes),
     )
-    .unwrap();
+    .map_err(|err| anyhow::Error::msg(format!("Failed to open '{source_name}': {err}")))
+}
 
-    let mut raw_buffer = [0; BUFFER_SIZE * 4];
-    loop {
-        // capture raw bytes
-        s.read(&mut raw_buffer)
-            .expect("Error reading from audio stream");
-
-        // convert to f32 format
-        match shared_buffer.try_lock() {
-            Ok(mut buffer) => {
-                for sample in raw_buffer.as_f32_samples() {
-                    buffer.push(sample);
+// Blocking reads happen on their own thread, so a suspended or silent source can't hold up
+// the switch to a new default. Once the receiver is dropped the thread ends after its next read.
+fn spawn_reader(stream: Simple) -> Reader {
+    let (sender, chunks) = mpsc::channel();
+    let (spare, spare_receiver) = mpsc::channel::<Vec<f32>>();
+    thread::spawn(move || {
+        let mut raw_buffer = [0; BUFFER_SIZE * 4];
+        let mut decoder = SampleDecoder::new(SampleFormat::F32LE, 1);
+        loop {
+            let chunk = match stream.read(&mut raw_buffer) {
+                Ok(()) => {
+                    // only allocates until the first buffers come back
+                    let mut samples = spare_receiver
+                        .try_recv()
+                        .unwrap_or_else(|_| Vec::with_capacity(BUFFER_SIZE));
+                    decoder.decode_into(&raw_buffer, &mut samples);
+                    Ok(samples)
                 }
+                Err(err) => Err(format!("Error reading from audio stream: {err}")),
+            };
+            let failed = chunk.is_err();
+            if sender.send(chunk).is_err() || failed {
+                return;
             }
-            Err(_) => {
-                continue;
+        }
+    });
+    Reader { chunks, spare }
+}
+
+// Pushes the read samples until the stream fails (Err) or the default device changes (Ok)
+fn record(
+    reader: &Reader,
+    shared_buffer: &Arc<Mutex<AudioProcessBuffer>>,
+    defaults_generation: &AtomicUsize,
+    generation: usize,
+) -> Result<(), anyhow::Error> {
+    while defaults_generation.load(Ordering::Relaxed) == generation {
+        let samples = match reader.chunks.recv_timeout(GENERATION_POLL) {
+            Ok(chunk) => chunk.map_err(anyhow::Error::msg)?,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(anyhow::Error::msg("Audio stream reader stopped"))
+            }
+        };
+        if let Ok(mut buffer) = shared_buffer.try_lock() {
+            for sample in samples.iter() {
+                buffer.push(*sample);
             }
         }
+        // the reader may already be gone
+        let _ = reader.spare.send(samples);
     }
+    Ok(())
 }
 
-fn get_default_sink_name() -> String {
-    /* Finds the name of default sink (audio output device)
-        1. Start pulse audio context,
-        2. Query the context's server for default sink name
-        3. Return default sink name
-    */
-    let mainloop = Rc::new(RefCell::new(
-        Mainloop::new().expect("Failed to create mainloop"),
-    ));
-
-    let context = Rc::new(RefCell::new(
-        Context::new(mainloop.borrow().deref(), "Default Audio Sink Context")
-            .expect("Failed to create new context"),
-    ));
+fn set_status(shared_buffer: &Arc<Mutex<AudioProcessBuffer>>, status: InputStatus) {
+    if let Ok(mut buffer) = shared_buffer.lock() {
+        buffer.status = status;
+    }
+}
 
-    context
-        .borrow_mut()
-        .connect(None, ContextFlagSet::NOFLAGS, None)
-        .expect("Failed to connect context");
+// Bumps the generation counter whenever the server's default sink or source changes.
+// Reconnects with backoff if the server restarts.
+fn default_device_watcher(defaults_generation: Arc<AtomicUsize>) {
+    let mut backoff = Backoff::new();
+    loop {
+        if let Ok(introspector) = Introspector::connect() {
+            backoff.reset();
+            // Only returns once the connection to the server is lost
+            let _ = follow_defaults(&introspector, &defaults_generation);
+        }
+        thread::sleep(backoff.wait());
+    }
+}
 
-    // Wait for context to be ready
+fn follow_defaults(
+    introspector: &Introspector,
+    defaults_generation: &AtomicUsize,
+) -> Result<(), anyhow::Error> {
+    let server_changed = introspector.subscribe_server_changes();
+    let mut defaults = introspector.server_defaults()?;
     loop {
-        mainloop.borrow_mut().iterate(true);
-        match context.borrow().get_state() {
-            pulse::context::State::Ready => {
-                break;
+        introspector.iterate()?;
+        if server_changed.replace(false) {
+            let new_defaults = introspector.server_defaults()?;
+            if new_defaults != defaults {
+                defaults = new_defaults;
+                defaults_generation.fetch_add(1, Ordering::Relaxed);
             }
-            pulse::context::State::Failed | pulse::context::State::Terminated => {
-                eprintln!("Context state failed/terminated, quitting...");
-                return String::from("Failed");
+        }
+    }
+}
+
+// Picks the source to record from. Sinks are recorded through their monitor source.
+fn resolve_source(input: &InputSettings) -> Result<String, anyhow::Error> {
+    let introspector = Introspector::connect()?;
+
+    let Some(device) = &input.device else {
+        let (default_sink, default_source) = introspector.server_defaults()?;
+        return match input.capture {
+            CaptureMode::Monitor => {
+                let sinks = introspector.sinks()?;
+                Ok(sinks
+                    .into_iter()
+                    .find(|sink| sink.name == default_sink)
+                    .and_then(|sink| sink.monitor_source)
+                    .unwrap_or(format!("{default_sink}.monitor")))
             }
-            _ => {}
+            CaptureMode::Microphone => Ok(default_source),
+        };
+    };
+
+    if introspector
+        .sources()?
+        .iter()
+        .any(|source| source.name == *device)
+    {
+        return Ok(device.clone());
+    }
+    if let Some(sink) = introspector
+        .sinks()?
+        .into_iter()
+        .find(|sink| sink.name == *device)
+    {
+        return sink.monitor_source.ok_or(anyhow::Error::msg(format!(
+            "Sink '{device}' has no monitor source"
+        )));
+    }
+    Err(anyhow::Error::msg(format!(
+        "Unknown PulseAudio device '{device}', see --list-devices"
+    )))
+}
+
+pub fn list_devices() -> Result<(), anyhow::Error> {
+    let introspector = Introspector::connect()?;
+    let (default_sink, default_source) = introspector.server_defaults()?;
+
+    println!("Sources:");
+    for source in introspector.sources()? {
+        let mut notes = vec![];
+        if source.name == default_source {
+            notes.push("default");
         }
+        if source.is_monitor {
+            notes.push("monitor");
+        }
+        print_device(&source.name, &source.description, &notes);
     }
 
-    let name_write_ptr = Arc::new(Mutex::new(String::new()));
-    let name_read_ptr = name_write_ptr.clone();
+    println!("Sinks (recorded through their monitor):");
+    for sink in introspector.sinks()? {
+        let notes = if sink.name == default_sink {
+            vec!["default"]
+        } else {
+            vec![]
+        };
+        print_device(&sink.name, &sink.description, &notes);
+    }
+    Ok(())
+}
 
-    let operation = context.borrow().introspect().get_server_info(move |info| {
-        let mut name_ref = name_write_ptr.lock().expect("Error acquiring mutex lock");
-        *name_ref = String::from(
-            info.default_sink_name
-                .as_ref()
-                .expect("Error parsing default sink name")
-                .deref(),
-        );
-    });
-    while operation.get_state() != pulse::operation::State::Done {
-        mainloop.borrow_mut().iterate(true);
+fn print_device(name: &str, description: &str, notes: &[&str]) {
+    if notes.is_empty() {
+        println!("  {name}\n      {description}");
+    } else {
+        println!("  {name} ({})\n      {description}", notes.join(", "));
+    }
+}
+
+struct SinkDevice {
+    name: String,
+    description: String,
+    monitor_source: Option<String>,
+}
+
+struct SourceDevice {
+    name: String,
+    description: String,
+    is_monitor: bool,
+}
+
+// Short-lived context for querying the server about its devices
+struct Introspector {
+    mainloop: Rc<RefCell<Mainloop>>,
+    context: Rc<RefCell<Context>>,
+}
+
+impl Introspector {
+    fn connect() -> Result<Introspector, anyhow::Error> {
+        let mainloop = Rc::new(RefCell::new(
+            Mainloop::new().ok_or(anyhow::Error::msg("Failed to create mainloop"))?,
+        ));
+
+        let context = Rc::new(RefCell::new(
+            Context::new(mainloop.borrow().deref(), "Audio Device Context")
+                .ok_or(anyhow::Error::msg("Failed to create new context"))?,
+        ));
+
+        context
+            .borrow_mut()
+            .connect(None, ContextFlagSet::NOFLAGS, None)
+            .map_err(|err| anyhow::Error::msg(format!("Failed to connect context: {err}")))?;
+
+        // Wait for context to be ready
+        loop {
+            mainloop.borrow_mut().iterate(true);
+            match context.borrow().get_state() {
+                pulse::context::State::Ready => {
+                    break;
+                }
+                pulse::context::State::Failed | pulse::context::State::Terminated => {
+                    return Err(anyhow::Error::msg(
+                        "PulseAudio context failed/terminated, is the server running?",
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Introspector { mainloop, context })
+    }
+
+    // Runs one mainloop iteration, fails once the connection to the server is gone
+    fn iterate(&self) -> Result<(), anyhow::Error> {
+        self.mainloop.borrow_mut().iterate(true);
+        match self.context.borrow().get_state() {
+            pulse::context::State::Failed | pulse::context::State::Terminated => Err(
+                anyhow::Error::msg("Lost connection to the PulseAudio server"),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    // Returns a flag that is raised on every server change event, e.g. a new default sink
+    fn subscribe_server_changes(&self) -> Rc<Cell<bool>> {
+        let server_changed = Rc::new(Cell::new(false));
+        let server_changed_writer = server_changed.clone();
+
+        let mut context = self.context.borrow_mut();
+        context.set_subscribe_callback(Some(Box::new(move |facility, _operation, _index| {
+            if facility == Some(Facility::Server) {
+                server_changed_writer.set(true);
+            }
+        })));
+        context.subscribe(InterestMaskSet::SERVER, |_success| {});
+        server_changed
+    }
+
+    fn wait_for<F: ?Sized>(&self, operation: Operation<F>) -> Result<(), anyhow::Error> {
+        loop {
+            match operation.get_state() {
+                OperationState::Done => return Ok(()),
+                OperationState::Cancelled => {
+                    return Err(anyhow::Error::msg("PulseAudio query was cancelled"))
+                }
+                OperationState::Running => {
+                    self.mainloop.borrow_mut().iterate(true);
+                }
+            }
+        }
+    }
+
+    // Returns the (default sink, default source) names
+    fn server_defaults(&self) -> Result<(String, String), anyhow::Error> {
+        let defaults = Rc::new(RefCell::new((String::new(), String::new())));
+        let defaults_writer = defaults.clone();
+
+        let operation = self
+            .context
+            .borrow()
+            .introspect()
+            .get_server_info(move |info| {
+                let mut defaults = defaults_writer.borrow_mut();
+                if let Some(sink) = info.default_sink_name.as_ref() {
+                    defaults.0 = sink.to_string();
+                }
+                if let Some(source) = info.default_source_name.as_ref() {
+                    defaults.1 = source.to_string();
+                }
+            });
+        self.wait_for(operation)?;
+
+        let defaults = defaults.borrow().clone();
+        Ok(defaults)
     }
 
-    // Clean shutdown
-    mainloop.borrow_mut().quit(Retval(0));
+    fn sinks(&self) -> Result<Vec<SinkDevice>, anyhow::Error> {
+        let sinks = Rc::new(RefCell::new(vec![]));
+        let sinks_writer = sinks.clone();
 
-    let name_ref = name_read_ptr.lock().expect("Error acquiring mutex lock");
-    name_ref.clone()
+        let operation = self
+            .context
+            .borrow()
+            .introspect()
+            .get_sink_info_list(move |result| {
+                if let ListResult::Item(info) = result {
+                    sinks_writer.borrow_mut().push(SinkDevice {
+                        name: info.name.as_deref().unwrap_or_default().to_string(),
+                        description: info.description.as_deref().unwrap_or_default().to_string(),
+                        monitor_source: info.monitor_source_name.as_deref().map(String::from),
+                    });
+                }
+            });
+        self.wait_for(operation)?;
+
+        Ok(sinks.take())
+    }
+
+    fn sources(&self) -> Result<Vec<SourceDevice>, anyhow::Error> {
+        let sources = Rc::new(RefCell::new(vec![]));
+        let sources_writer = sources.clone();
+
+        let operation = self
+            .context
+            .borrow()
+            .introspect()
+            .get_source_info_list(move |result| {
+                if let ListResult::Item(info) = result {
+                    sources_writer.borrow_mut().push(SourceDevice {
+                        name: info.name.as_deref().unwrap_or_default().to_string(),
+                        description: info.description.as_deref().unwrap_or_default().to_string(),
+                        is_monitor: info.monitor_of_sink.is_some(),
+                    });
+                }
+            });
+        self.wait_for(operation)?;
+
+        Ok(sources.take())
+    }
+}
+
+impl Drop for Introspector {
+    fn drop(&mut self) {
+        // Clean shutdown
+        self.context.borrow_mut().disconnect();
+        self.mainloop.borrow_mut().quit(Retval(0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn status(shared_buffer: &Arc<Mutex<AudioProcessBuffer>>) -> InputStatus {
+        shared_buffer.lock().unwrap().status.clone()
+    }
+
+    // A reader fed by the test instead of a stream, with the returned buffers
+    fn reader(chunks: Receiver<Chunk>) -> (Reader, Receiver<Vec<f32>>) {
+        let (spare, returned) = mpsc::channel();
+        (Reader { chunks, spare }, returned)
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max_and_resets() {
+        let mut backoff = Backoff::new();
+        let waits: Vec<u128> = (0..8).map(|_| backoff.wait().as_millis()).collect();
+        assert_eq!(waits, [250, 500, 1000, 2000, 4000, 8000, 8000, 8000]);
+        backoff.reset();
+        assert_eq!(backoff.wait(), MIN_BACKOFF);
+    }
+
+    #[test]
+    fn failed_connects_back_off_until_one_succeeds() {
+        let shared_buffer = Arc::new(Mutex::new(AudioProcessBuffer::default()));
+        let generation = Arc::new(AtomicUsize::new(0));
+        let mut backoff = Backoff::new();
+
+        let mut waits = vec![];
+        for _ in 0..3 {
+            let wait = session(&shared_buffer, &generation, &mut backoff, || {
+                Err(anyhow::Error::msg("Connection refused"))
+            });
+            waits.push(wait.unwrap().as_millis());
+            assert!(matches!(
+                status(&shared_buffer),
+                InputStatus::Disconnected(_)
+            ));
+        }
+        assert_eq!(waits, [250, 500, 1000]);
+
+        // the server is back, the default device changes while recording
+        let (sender, chunks) = mpsc::channel();
+        sender.send(Ok(vec![0.5; 64])).unwrap();
+        let generation_writer = generation.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            generation_writer.fetch_add(1, Ordering::Relaxed);
+        });
+        let (reader, _returned) = reader(chunks);
+        let wait = session(&shared_buffer, &generation, &mut backoff, || {
+            Ok((String::from("sink.monitor"), reader))
+        });
+        assert_eq!(wait, None);
+        assert_eq!(status(&shared_buffer), InputStatus::Connecting);
+        drop(sender);
+
+        // a successful connection starts the backoff over
+        let wait = session(&shared_buffer, &generation, &mut backoff, || {
+            Err(anyhow::Error::msg("Connection refused"))
+        });
+        assert_eq!(wait, Some(MIN_BACKOFF));
+    }
+
+    #[test]
+    fn silent_stream_doesnt_stall_default_change() {
+        let shared_buffer = Arc::new(Mutex::new(AudioProcessBuffer::default()));
+        let generation = Arc::new(AtomicUsize::new(0));
+        // the stream never delivers anything, like a suspended source
+        let (_sender, chunks) = mpsc::channel();
+        let generation_writer = generation.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            generation_writer.fetch_add(1, Ordering::Relaxed);
+        });
+
+        let start = Instant::now();
+        let (reader, _returned) = reader(chunks);
+        let wait = session(&shared_buffer, &generation, &mut Backoff::new(), || {
+            Ok((String::from("suspended.monitor"), reader))
+        });
+        assert_eq!(wait, None);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn read_errors_are_retried() {
+        let shared_buffer = Arc::new(Mutex::new(AudioProcessBuffer::default()));
+        let generation = Arc::new(AtomicUsize::new(0));
+        let (sender, chunks) = mpsc::channel();
+        sender.send(Ok(vec![0.0; 64])).unwrap();
+        sender.send(Err(String::from("device unplugged"))).unwrap();
+
+        let (reader, returned) = reader(chunks);
+        let wait = session(&shared_buffer, &generation, &mut Backoff::new(), || {
+            Ok((String::from("usb.monitor"), reader))
+        });
+        assert_eq!(wait, Some(MIN_BACKOFF));
+        // the pushed samples went back for the next read
+        assert_eq!(returned.try_recv().unwrap().capacity(), 64);
+        match status(&shared_buffer) {
+            InputStatus::Disconnected(reason) => assert!(reason.contains("device unplugged")),
+            status => panic!("unexpected status {status:?}"),
+        }
+    }
 }
@@ -0,0 +1,164 @@
+// Splits the terminal into regions that each run their own animators, side by side,
+// stacked, in a grid, with an optional full width strip of fixed height at the bottom.
+
+use std::str::FromStr;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum Split {
+    // Regions side by side
+    #[default]
+    Columns,
+    // Regions on top of each other
+    Rows,
+    // Regions filled in row by row, this many per row
+    Grid(usize),
+}
+
+impl FromStr for Split {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "columns" => Ok(Split::Columns),
+            "rows" => Ok(Split::Rows),
+            "grid" => Ok(Split::Grid(2)),
+            _ => Err(format!(
+                "Unknown split '{s}' (expected 'columns', 'rows' or 'grid')"
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RegionSettings {
+    // Source every animation in the region reads, None keeps each animation's own
+    pub source: Option<String>,
+    // Plays only this animation instead of the rotation
+    pub animation: Option<String>,
+    // Share of the width or height relative to the other regions, unused in a grid
+    pub size: f32,
+}
+
+impl Default for RegionSettings {
+    fn default() -> Self {
+        RegionSettings {
+            source: None,
+            animation: None,
+            size: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LayoutSettings {
+    pub split: Split,
+    pub regions: Vec<RegionSettings>,
+    // Full width region below the others
+    pub strip: Option<RegionSettings>,
+    pub strip_height: usize,
+}
+
+impl Default for LayoutSettings {
+    fn default() -> Self {
+        LayoutSettings {
+            split: Split::Columns,
+            regions: vec![RegionSettings::default()],
+            strip: None,
+            strip_height: 4,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl LayoutSettings {
+    // Every region and then the strip, in the same order as `all_regions`
+    pub fn rects(&self, width: usize, height: usize) -> Vec<Rect> {
+        let strip_height = match self.strip {
+            Some(_) => self.strip_height.min(height),
+            None => 0,
+        };
+        let height = height - strip_height;
+        let sizes: Vec<f32> = self.regions.iter().map(|region| region.size).collect();
+
+        let mut rects: Vec<Rect> = match self.split {
+            Split::Columns => spans(width, &sizes)
+                .into_iter()
+                .map(|(x, width)| Rect {
+                    x,
+                    y: 0,
+                    width,
+                    height,
+                })
+                .collect(),
+            Split::Rows => spans(height, &sizes)
+                .into_iter()
+                .map(|(y, height)| Rect {
+                    x: 0,
+                    y,
+                    width,
+                    height,
+                })
+                .collect(),
+            Split::Grid(columns) => {
+                let columns = columns.clamp(1, self.regions.len().max(1));
+                let rows = self.regions.len().div_ceil(columns);
+                let xs = spans(width, &vec![1.0; columns]);
+                let ys = spans(height, &vec![1.0; rows]);
+                (0..self.regions.len())
+                    .map(|i| {
+                        let (x, width) = xs[i % columns];
+                        let (y, height) = ys[i / columns];
+                        Rect {
+                            x,
+                            y,
+                            width,
+                            height,
+                        }
+                    })
+                    .collect()
+            }
+        };
+        if self.strip.is_some() {
+            rects.push(Rect {
+                x: 0,
+                y: height,
+                width,
+                height: strip_height,
+            });
+        }
+        rects
+    }
+
+    pub fn all_regions(&self) -> impl Iterator<Item = &RegionSettings> {
+        self.regions.iter().chain(self.strip.iter())
+    }
+}
+
+// Cuts `total` cells into (offset, length) spans proportional to `sizes`
+fn spans(total: usize, sizes: &[f32]) -> Vec<(usize, usize)> {
+    let sum: f32 = sizes.iter().sum();
+    let mut covered = 0.0;
+    let mut start = 0;
+    sizes
+        .iter()
+        .map(|size| {
+            covered += size;
+            let end = if sum > 0.0 {
+                ((total as f32) * covered / sum).round() as usize
+            } else {
+                total
+            };
+            let end = end.min(total);
+            let span = (start, end - start);
+            start = end;
+            span
+        })
+        .collect()
+}
@@ -0,0 +1,18 @@
+pub mod animators;
+pub mod audio_formats;
+pub mod audio_processing;
+pub mod colors;
+pub mod compositor;
+pub mod config;
+pub mod feature_log;
+pub mod filters;
+pub mod input;
+pub mod layout;
+pub mod preprocess;
+pub mod resample;
+pub mod scheduler;
+pub mod spectrum;
+pub mod surface;
+pub mod tap;
+pub mod terminal_grid;
+pub mod transitions;
@@ -1,63 +1,388 @@
-use animators::Animators;
+use clap::Parser;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::terminal;
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 use std::{thread, time};
 
-use audio_processing::AudioFeatures;
-use config::Config;
-use terminal_grid::TerminalGrid;
-
-pub mod animators;
-pub mod audio_formats;
-pub mod audio_processing;
-pub mod colors;
-pub mod config;
-pub mod input;
-pub mod terminal_grid;
+use ascii_audio_visualizer::animators::{self, Animators};
+use ascii_audio_visualizer::audio_processing::{AudioFeatures, AudioProcessBuffer};
+use ascii_audio_visualizer::config::{CommandLineArgs, Config, MAIN_SOURCE};
+use ascii_audio_visualizer::feature_log::{self, FeatureRecorder};
+use ascii_audio_visualizer::input;
+use ascii_audio_visualizer::input::{InputHandle, InputStatus, Sources};
+use ascii_audio_visualizer::scheduler::Scheduler;
+use ascii_audio_visualizer::surface::{Surface, Viewport};
+use ascii_audio_visualizer::tap::{AudioTap, TapMode};
+use ascii_audio_visualizer::terminal_grid::TerminalGrid;
+use ascii_audio_visualizer::transitions::Transition;
 
 fn main() -> Result<(), anyhow::Error> {
-    let config = Config::load_config();
-    let animators: Animators = Animators::new(&config);
-    let mut grid = TerminalGrid::new(config.bg_color);
-    let mut audio_features: AudioFeatures;
-
-    if animators.list.is_empty() {
-        return Err(anyhow::Error::msg("Error: no active animations."));
+    let args = CommandLineArgs::parse();
+    if args.list_animations {
+        animators::list_animations();
+        return Ok(());
+    }
+    let config = Config::load_config(&args);
+    if args.list_devices {
+        return input::list_devices(&config.input);
     }
 
-    // Listen to audio via pulseaudio API on linux.
-    #[cfg(any(
-        target_os = "linux",
-        target_os = "dragonfly",
-        target_os = "freebsd",
-        target_os = "netbsd"
-    ))]
-    let process_buffer_reader = input::pulse::connect().expect("Failed to connect audio listener");
+    // Every region runs its own instances, so animators never share state between regions
+    let mut regions: Vec<Region> = config
+        .layout
+        .all_regions()
+        .map(|settings| Region {
+            animators: match &settings.animation {
+                Some(name) => Animators::fixed(&config, name),
+                None => Animators::new(&config),
+            },
+            source: settings.source.clone(),
+            fixed: settings.animation.is_some(),
+            surface: TerminalGrid::with_size(config.bg_color, 0, 0),
+            outgoing: TerminalGrid::with_size(config.bg_color, 0, 0),
+        })
+        .collect();
 
-    // Listen to audio via CPAL crate on windows.
-    #[cfg(target_os = "windows")]
-    let (process_buffer_reader, _stream) =
-        input::wasapi::connect().expect("Failed to connect audio listener");
+    if regions
+        .iter()
+        .any(|region| region.animators.list.is_empty())
+    {
+        return Err(anyhow::Error::msg("Error: no active animations."));
+    }
+    // The idle screen covers the whole terminal
+    let mut idle_animator = Animators::idle(&config);
+    let mut idle_size = (0, 0);
 
-    let animation_duration = config.animation_length as i32;
-    let num_animators = animators.list.len() as i32;
+    // Listen to audio via the configured backends (pulseaudio on linux, WASAPI on windows by default),
+    // or play back recorded features without touching the audio system
+    let sources = match &args.replay_features {
+        Some(path) => {
+            // a recording holds a single stream, the default source it was made from
+            if !config.sources.is_empty() {
+                return Err(anyhow::Error::msg(
+                    "Error: --replay-features plays back a single source, remove the [input.<name>] sections and the sources they are shown from.",
+                ));
+            }
+            Sources::single(MAIN_SOURCE, InputHandle::new(feature_log::replay(path)?))
+        }
+        None => {
+            let mut inputs = vec![(String::from(MAIN_SOURCE), config.input.clone())];
+            inputs.extend(config.sources.iter().cloned());
+            Sources::connect(config.analysis.clone(), &inputs)
+                .expect("Failed to connect audio listener")
+        }
+    };
+    // Layers of a scene can read a source of their own
+    for region in regions.iter_mut() {
+        region.animators.attach_sources(&sources);
+    }
+    if let Some(idle_animator) = idle_animator.as_mut() {
+        idle_animator.attach_sources(&sources);
+    }
+    // Recording and capture follow the default source, the mix when there are several
+    let default_buffer = sources.buffer(None);
+    if let Some(path) = &args.record_features {
+        let recorder = FeatureRecorder::create(path)?;
+        if let Ok(mut buffer) = default_buffer.lock() {
+            buffer.record_features(recorder);
+        }
+    }
+    // A replay has no audio to capture
+    let tap_mode = match args.replay_features {
+        Some(_) => TapMode::Off,
+        None => config.tap.mode,
+    };
+    if tap_mode != TapMode::Off {
+        let tap = AudioTap::new(config.tap.clone())?;
+        if let Ok(mut buffer) = default_buffer.lock() {
+            buffer.tap_audio(tap);
+        }
+    }
+    // Keys are only read when there is something to trigger, raw mode means quitting is handled here too
+    let hotkeys = tap_mode == TapMode::Rolling;
+    let _raw_mode = if hotkeys {
+        Some(RawMode::enable()?)
+    } else {
+        None
+    };
+    let mut grid = TerminalGrid::new(config.bg_color);
+    let mut notice: Option<(String, Instant)> = None;
+    let mut scheduler = Scheduler::new(&config);
     let start = Instant::now();
     let mut elapsed: f32;
-    loop {
-        thread::sleep(time::Duration::from_secs_f32(0.014));
+    let mut last_update = 0.0;
+    // Index of the animation on screen, None before the first frame
+    let mut shown: Option<usize> = None;
+    // The animation being transitioned away from, and when the switch happened
+    let mut outgoing: Option<(usize, f32)> = None;
+    let mut idle = false;
+    let mut status = InputStatus::Connecting;
+    'frame: loop {
+        // Drop the frame rate while idle, audio returning is picked up on the next frame
+        let frame_time = if idle { 1.0 / config.idle_fps } else { 0.014 };
+        thread::sleep(time::Duration::from_secs_f32(frame_time));
 
-        match process_buffer_reader.try_lock() {
-            Ok(buffer) => {
-                audio_features = buffer.features;
-            }
-            Err(_) => {
-                continue;
+        match read_hotkey(hotkeys)? {
+            Some(Hotkey::Quit) => return Ok(()),
+            Some(Hotkey::DumpCapture) => {
+                if let Some(path) = default_buffer
+                    .lock()
+                    .ok()
+                    .and_then(|buffer| buffer.dump_tap())
+                {
+                    notice = Some((format!(" saved {path} "), Instant::now()));
+                }
             }
+            None => {}
+        }
+        if let Some(error) = default_buffer
+            .lock()
+            .ok()
+            .and_then(|buffer| buffer.write_error())
+        {
+            notice = Some((format!(" {error} "), Instant::now()));
         }
 
         elapsed = start.elapsed().as_secs_f32();
-        let animator_idx = (elapsed as i32 / animation_duration) % num_animators;
-        let animator_idx = animator_idx as usize;
-        animators.list[animator_idx](&config, &audio_features, elapsed, &mut grid);
+        // Onsets and energy changes are detected on the default source
+        let Some(schedule_features) = read_features(default_buffer, None) else {
+            continue;
+        };
+        let animator_idx = scheduler.update(elapsed, &schedule_features);
+        if shown.is_none() {
+            // Regions with a fixed animation never switch, they start once
+            for region in regions.iter_mut().filter(|region| region.fixed) {
+                region.animators.list[0].animator.on_enter();
+            }
+        }
+        if shown != Some(animator_idx) {
+            // A transition that is still running is cut short
+            if let Some((previous, _)) = outgoing.take() {
+                for region in regions.iter_mut().filter(|region| !region.fixed) {
+                    region.animators.list[previous].animator.on_exit();
+                }
+            }
+            for region in regions.iter_mut().filter(|region| !region.fixed) {
+                region.animators.list[animator_idx].animator.on_enter();
+            }
+            outgoing = shown.map(|previous| (previous, elapsed));
+            shown = Some(animator_idx);
+        }
+        // The animation being replaced keeps running until the transition is over
+        let transition = outgoing.map(|(previous, started)| {
+            let progress = match config.transition {
+                Transition::Cut => 1.0,
+                _ => (elapsed - started) / config.transition_length,
+            };
+            (previous, progress)
+        });
+        let transition = transition.filter(|(_, progress)| *progress < 1.0);
+        if transition.is_none() {
+            if let Some((previous, _)) = outgoing.take() {
+                for region in regions.iter_mut().filter(|region| !region.fixed) {
+                    region.animators.list[previous].animator.on_exit();
+                }
+            }
+        }
+
+        // One set of features per region, and for the outgoing animation during a transition
+        let mut region_features: Vec<(AudioFeatures, Option<AudioFeatures>)> =
+            Vec::with_capacity(regions.len());
+        for region in regions.iter() {
+            let Some(features) = region.features(&sources, animator_idx) else {
+                continue 'frame;
+            };
+            let outgoing_features = match transition.filter(|_| !region.fixed) {
+                Some((previous, _)) => match region.features(&sources, previous) {
+                    Some(features) => Some(features),
+                    None => continue 'frame,
+                },
+                None => None,
+            };
+            region_features.push((features, outgoing_features));
+        }
+        if let Some(source_status) = sources.status(regions[0].source_name(animator_idx)) {
+            status = source_status;
+        }
+        let dt = elapsed - last_update;
+        last_update = elapsed;
+
+        let was_idle = idle;
+        idle = region_features.iter().all(|(features, _)| features.silent);
+        if idle {
+            match idle_animator.as_mut() {
+                Some(idle_animator) => {
+                    if !was_idle {
+                        idle_animator.on_enter();
+                    }
+                    if idle_size != (grid.width, grid.height) {
+                        idle_size = (grid.width, grid.height);
+                        idle_animator.on_resize(grid.width, grid.height);
+                    }
+                    idle_animator.update(dt, &region_features[0].0);
+                    idle_animator.render(&mut grid);
+                }
+                None if !was_idle => grid.dim(0.6),
+                None => continue,
+            }
+            grid.display();
+            continue;
+        }
+        if was_idle {
+            if let Some(idle_animator) = idle_animator.as_mut() {
+                idle_animator.on_exit();
+            }
+        }
+
+        // A grid with fewer regions than cells leaves part of the screen uncovered
+        grid.clear();
+        let rects = config.layout.rects(grid.width, grid.height);
+        for ((region, (features, outgoing_features)), rect) in
+            regions.iter_mut().zip(region_features.iter()).zip(rects)
+        {
+            region.resize(rect.width, rect.height);
+            let mut viewport = Viewport::new(&mut grid, rect.x, rect.y, rect.width, rect.height);
+
+            let current = region.animation(animator_idx);
+            let animator = &mut region.animators.list[current].animator;
+            animator.update(dt, features);
+            match (transition, outgoing_features) {
+                (Some((previous, progress)), Some(outgoing_features)) => {
+                    animator.render(&mut region.surface);
+                    let animator = &mut region.animators.list[previous].animator;
+                    animator.update(dt, outgoing_features);
+                    animator.render(&mut region.outgoing);
+                    config.transition.blend(
+                        &region.outgoing,
+                        &region.surface,
+                        progress,
+                        &mut viewport,
+                    );
+                }
+                _ => animator.render(&mut viewport),
+            }
+        }
+        draw_status(&config, &status, &mut grid);
+        if let Some((message, shown_at)) = &notice {
+            if shown_at.elapsed().as_secs_f32() < NOTICE_DURATION_S {
+                let y = grid.height.saturating_sub(1);
+                grid.draw_text(message, config.color_2, 0, y);
+            }
+        }
         grid.display();
     }
 }
+
+// A region of the layout, with the animators drawn into it and the source it reads
+struct Region {
+    animators: Animators,
+    // Overrides the source of every animation, e.g. one side of a split screen
+    source: Option<String>,
+    // Plays its one animation instead of following the rotation
+    fixed: bool,
+    // During a transition both animations are drawn separately, then blended onto the screen
+    surface: TerminalGrid,
+    outgoing: TerminalGrid,
+}
+
+impl Region {
+    fn resize(&mut self, width: usize, height: usize) {
+        if (self.surface.width, self.surface.height) == (width, height) {
+            return;
+        }
+        self.surface.resize(width, height);
+        self.outgoing.resize(width, height);
+        self.animators.resize(width, height);
+    }
+
+    // Which of this region's animations plays while the rotation is at `rotation`
+    fn animation(&self, rotation: usize) -> usize {
+        if self.fixed {
+            0
+        } else {
+            rotation
+        }
+    }
+
+    fn source_name(&self, rotation: usize) -> Option<&str> {
+        let animation = &self.animators.list[self.animation(rotation)];
+        self.source.as_deref().or(animation.source.as_deref())
+    }
+
+    // The features this region's animation reads while the rotation is at `rotation`
+    fn features(&self, sources: &Sources, rotation: usize) -> Option<AudioFeatures> {
+        let view = self.animators.list[self.animation(rotation)]
+            .view
+            .as_deref();
+        read_features(sources.buffer(self.source_name(rotation)), view)
+    }
+}
+
+// Features of a source, or of one of its filtered views. None while the backend holds the lock.
+fn read_features(
+    buffer: &Arc<Mutex<AudioProcessBuffer>>,
+    view: Option<&str>,
+) -> Option<AudioFeatures> {
+    let buffer = buffer.try_lock().ok()?;
+    Some(match view {
+        Some(view) => *buffer.view_features(view).unwrap_or(&buffer.features),
+        None => buffer.features,
+    })
+}
+
+const NOTICE_DURATION_S: f32 = 3.0;
+
+// Keeps the terminal in raw mode while alive, so errors and panics leave it usable too
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> Result<RawMode, anyhow::Error> {
+        terminal::enable_raw_mode()?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+enum Hotkey {
+    Quit,
+    DumpCapture,
+}
+
+// Reads pending key presses without blocking, None when hotkeys are off or nothing matched
+fn read_hotkey(enabled: bool) -> Result<Option<Hotkey>, anyhow::Error> {
+    if !enabled {
+        return Ok(None);
+    }
+    while event::poll(time::Duration::ZERO)? {
+        if let Event::Key(KeyEvent {
+            code, modifiers, ..
+        }) = event::read()?
+        {
+            match code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(Some(Hotkey::Quit)),
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(Some(Hotkey::Quit))
+                }
+                KeyCode::Char('d') => return Ok(Some(Hotkey::DumpCapture)),
+                _ => {}
+            }
+        }
+    }
+    Ok(None)
+}
+
+// Shows the capture backend's state in the top row while it isn't delivering audio
+fn draw_status(config: &Config, status: &InputStatus, grid: &mut TerminalGrid) {
+    let message = match status {
+        InputStatus::Connected(_) => return,
+        InputStatus::Connecting => String::from(" audio: connecting... "),
+        InputStatus::Disconnected(reason) => format!(" audio: disconnected ({reason}) "),
+    };
+    grid.draw_text(&message, config.color_2, 0, 0);
+}
@@ -0,0 +1,229 @@
+// Optional clean-up stages applied to incoming samples before analysis:
+// DC-blocking high-pass -> noise gate -> pre-emphasis
+
+// Pole of the DC blocker, closer to 1.0 means a lower cutoff (~4Hz at 48kHz)
+const DC_BLOCKER_POLE: f32 = 0.9995;
+const GATE_ATTACK_S: f32 = 0.001;
+
+#[derive(Copy, Clone)]
+pub struct PreprocessSettings {
+    pub dc_block: bool,
+    // Signals whose envelope stays below this level (dBFS) are muted
+    pub gate_threshold_db: Option<f32>,
+    pub gate_release_s: f32,
+    // Pre-emphasis coefficient, typically 0.9 to 0.97
+    pub pre_emphasis: Option<f32>,
+}
+
+impl Default for PreprocessSettings {
+    fn default() -> Self {
+        PreprocessSettings {
+            dc_block: false,
+            gate_threshold_db: None,
+            gate_release_s: 0.1,
+            pre_emphasis: None,
+        }
+    }
+}
+
+pub struct Preprocessor {
+    dc_blocker: Option<DcBlocker>,
+    noise_gate: Option<NoiseGate>,
+    pre_emphasis: Option<PreEmphasis>,
+}
+
+impl Preprocessor {
+    pub fn new(settings: PreprocessSettings, sample_rate: f32) -> Preprocessor {
+        Preprocessor {
+            dc_blocker: settings.dc_block.then(DcBlocker::new),
+            noise_gate: settings.gate_threshold_db.map(|threshold_db| {
+                NoiseGate::new(threshold_db, settings.gate_release_s, sample_rate)
+            }),
+            pre_emphasis: settings.pre_emphasis.map(PreEmphasis::new),
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        let mut sample = sample;
+        if let Some(dc_blocker) = self.dc_blocker.as_mut() {
+            sample = dc_blocker.process(sample);
+        }
+        if let Some(noise_gate) = self.noise_gate.as_mut() {
+            sample = noise_gate.process(sample);
+        }
+        if let Some(pre_emphasis) = self.pre_emphasis.as_mut() {
+            sample = pre_emphasis.process(sample);
+        }
+        sample
+    }
+}
+
+// One-pole, one-zero high-pass: y[n] = x[n] - x[n-1] + R * y[n-1]
+pub struct DcBlocker {
+    prev_in: f32,
+    prev_out: f32,
+}
+
+impl DcBlocker {
+    pub fn new() -> DcBlocker {
+        DcBlocker {
+            prev_in: 0.0,
+            prev_out: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        let out = sample - self.prev_in + DC_BLOCKER_POLE * self.prev_out;
+        self.prev_in = sample;
+        self.prev_out = out;
+        out
+    }
+}
+
+impl Default for DcBlocker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Mutes the signal while its peak envelope is below the threshold.
+// The gain ramps with the attack/release times so opening and closing doesn't click.
+pub struct NoiseGate {
+    threshold: f32,
+    envelope: f32,
+    gain: f32,
+    attack_coef: f32,
+    release_coef: f32,
+}
+
+impl NoiseGate {
+    pub fn new(threshold_db: f32, release_s: f32, sample_rate: f32) -> NoiseGate {
+        let coef = |time_s: f32| (-1.0 / (time_s.max(1e-6) * sample_rate)).exp();
+        NoiseGate {
+            threshold: 10f32.powf(threshold_db / 20.0),
+            envelope: 0.0,
+            gain: 0.0,
+            attack_coef: coef(GATE_ATTACK_S),
+            release_coef: coef(release_s),
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        // peak envelope follower
+        let level = sample.abs();
+        let coef = if level > self.envelope {
+            self.attack_coef
+        } else {
+            self.release_coef
+        };
+        self.envelope = level + coef * (self.envelope - level);
+
+        let target = if self.envelope >= self.threshold {
+            1.0
+        } else {
+            0.0
+        };
+        let coef = if target > self.gain {
+            self.attack_coef
+        } else {
+            self.release_coef
+        };
+        self.gain = target + coef * (self.gain - target);
+        sample * self.gain
+    }
+}
+
+// First-order pre-emphasis: y[n] = x[n] - a * x[n-1], tilts the spectrum towards the treble
+pub struct PreEmphasis {
+    coefficient: f32,
+    prev_in: f32,
+}
+
+impl PreEmphasis {
+    pub fn new(coefficient: f32) -> PreEmphasis {
+        PreEmphasis {
+            coefficient,
+            prev_in: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        let out = sample - self.coefficient * self.prev_in;
+        self.prev_in = sample;
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FS: f32 = 48000.0;
+
+    fn sine(freq: f32, amplitude: f32, n: usize) -> impl Iterator<Item = f32> {
+        (0..n).map(move |i| amplitude * (2.0 * std::f32::consts::PI * freq * i as f32 / FS).sin())
+    }
+
+    #[test]
+    fn dc_blocker_removes_offset() {
+        let mut dc_blocker = DcBlocker::new();
+        let out: Vec<f32> = sine(440.0, 0.5, 48000)
+            .map(|sample| dc_blocker.process(sample + 0.3))
+            .collect();
+        // skip the first half second while the filter settles
+        let settled = &out[24000..];
+        let mean = settled.iter().sum::<f32>() / settled.len() as f32;
+        assert!(mean.abs() < 0.01, "mean {mean}");
+        let peak = settled.iter().fold(0.0, |peak: f32, s| peak.max(s.abs()));
+        assert!((peak - 0.5).abs() < 0.05, "peak {peak}");
+    }
+
+    #[test]
+    fn gate_stays_closed_below_threshold() {
+        let mut gate = NoiseGate::new(-40.0, 0.1, FS);
+        // -46 dBFS
+        let peak = sine(440.0, 0.005, 48000)
+            .map(|sample| gate.process(sample))
+            .fold(0.0, |peak: f32, s| peak.max(s.abs()));
+        assert!(peak < 1e-4, "peak {peak}");
+    }
+
+    #[test]
+    fn gate_opens_above_threshold() {
+        let mut gate = NoiseGate::new(-40.0, 0.1, FS);
+        // -20 dBFS, the gate is fully open after the attack
+        let out: Vec<f32> = sine(440.0, 0.1, 48000)
+            .map(|sample| gate.process(sample))
+            .collect();
+        let input: Vec<f32> = sine(440.0, 0.1, 48000).collect();
+        for (out, input) in out.iter().zip(input.iter()).skip(4800) {
+            assert!((out - input).abs() < 1e-4, "{out} != {input}");
+        }
+    }
+
+    #[test]
+    fn gate_closes_after_release() {
+        let mut gate = NoiseGate::new(-40.0, 0.01, FS);
+        sine(440.0, 0.1, 4800).for_each(|sample| {
+            gate.process(sample);
+        });
+        let tail = sine(440.0, 0.001, 48000)
+            .map(|sample| gate.process(sample))
+            .skip(24000)
+            .fold(0.0, |peak: f32, s| peak.max(s.abs()));
+        assert!(tail < 1e-5, "tail {tail}");
+    }
+
+    #[test]
+    fn pre_emphasis_matches_difference_equation() {
+        let coefficient = 0.95;
+        let mut pre_emphasis = PreEmphasis::new(coefficient);
+        let input: Vec<f32> = sine(1000.0, 0.7, 256).collect();
+        let mut prev = 0.0;
+        for x in input {
+            let expected = x - coefficient * prev;
+            assert!((pre_emphasis.process(x) - expected).abs() < 1e-6);
+            prev = x;
+        }
+    }
+}
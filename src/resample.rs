@@ -0,0 +1,189 @@
+// Streaming windowed-sinc resampler, converts the input rate to the analysis rate.
+// The kernel is tabulated at PHASES sub-sample offsets and interpolated between them.
+
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+// Zero crossings of the sinc on each side, more means a steeper anti-aliasing filter
+const HALF_TAPS: usize = 16;
+const PHASES: usize = 256;
+// Cutoff relative to the lower Nyquist frequency, leaves room for the transition band
+const CUTOFF: f64 = 0.92;
+// Kaiser window shape, ~80dB stopband attenuation
+const KAISER_BETA: f64 = 8.0;
+
+pub struct Resampler {
+    // Input samples per output sample
+    step: f64,
+    // Kernel values for each phase, PHASES + 1 rows of 2 * HALF_TAPS taps
+    table: Vec<f32>,
+    history: VecDeque<f32>,
+    // Position of the next output sample, relative to history[0]
+    position: f64,
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, output_rate: u32) -> Resampler {
+        let step = input_rate as f64 / output_rate as f64;
+        // When downsampling the filter has to cut below the output's Nyquist frequency
+        let cutoff = CUTOFF * (1.0 / step).min(1.0);
+        let taps = 2 * HALF_TAPS;
+
+        let mut table = Vec::with_capacity((PHASES + 1) * taps);
+        for phase in 0..=PHASES {
+            let fraction = phase as f64 / PHASES as f64;
+            for tap in 0..taps {
+                // distance from the output position to this input sample
+                let t = tap as f64 - (HALF_TAPS as f64 - 1.0) - fraction;
+                let window = kaiser(t / HALF_TAPS as f64);
+                table.push((cutoff * sinc(cutoff * t) * window) as f32);
+            }
+        }
+
+        Resampler {
+            step,
+            table,
+            history: VecDeque::from(vec![0.0; 2 * HALF_TAPS]),
+            position: HALF_TAPS as f64 - 1.0,
+        }
+    }
+
+    // Feeds one input sample, calling `output` for each output sample it completes
+    pub fn process(&mut self, sample: f32, mut output: impl FnMut(f32)) {
+        self.history.push_back(sample);
+        let taps = 2 * HALF_TAPS;
+
+        // An output needs HALF_TAPS input samples after its position
+        while self.position + (HALF_TAPS as f64) < self.history.len() as f64 {
+            let first = self.position.floor() as usize + 1 - HALF_TAPS;
+            let fraction = self.position.fract() * PHASES as f64;
+            let phase = fraction.floor() as usize;
+            let blend = (fraction - phase as f64) as f32;
+
+            let row_a = &self.table[phase * taps..(phase + 1) * taps];
+            let row_b = &self.table[(phase + 1) * taps..(phase + 2) * taps];
+            let mut value = 0.0;
+            for (tap, input) in self.history.range(first..first + taps).enumerate() {
+                let weight = row_a[tap] + (row_b[tap] - row_a[tap]) * blend;
+                value += input * weight;
+            }
+            output(value);
+            self.position += self.step;
+        }
+
+        // Drop what no future output needs
+        let keep_from = (self.position.floor() as usize + 1).saturating_sub(HALF_TAPS);
+        self.history.drain(..keep_from);
+        self.position -= keep_from as f64;
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+// Kaiser window over [-1, 1]
+fn kaiser(x: f64) -> f64 {
+    if x.abs() > 1.0 {
+        return 0.0;
+    }
+    bessel_i0(KAISER_BETA * (1.0 - x * x).sqrt()) / bessel_i0(KAISER_BETA)
+}
+
+// Modified Bessel function of the first kind, order 0, by its power series
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    for k in 1..32 {
+        term *= (x / (2.0 * k as f64)).powi(2);
+        sum += term;
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One second of a sine at `freq`, resampled to 48kHz, with the filter's warm-up
+    // and the unfinished tail cut off
+    fn resampled_sine(input_rate: u32, freq: f64, amplitude: f64) -> Vec<f32> {
+        let mut resampler = Resampler::new(input_rate, 48_000);
+        let mut output = vec![];
+        for n in 0..input_rate {
+            let t = n as f64 / input_rate as f64;
+            let sample = (amplitude * (2.0 * PI * freq * t).sin()) as f32;
+            resampler.process(sample, |sample| output.push(sample));
+        }
+        output[100..output.len() - 100].to_vec()
+    }
+
+    // Amplitude of the component at `freq`, by correlating with a sine and cosine
+    fn amplitude_at(samples: &[f32], freq: f64) -> f64 {
+        let (mut re, mut im) = (0.0, 0.0);
+        for (n, sample) in samples.iter().enumerate() {
+            let phase = 2.0 * PI * freq * n as f64 / 48_000.0;
+            re += *sample as f64 * phase.cos();
+            im += *sample as f64 * phase.sin();
+        }
+        2.0 * (re * re + im * im).sqrt() / samples.len() as f64
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples
+            .iter()
+            .fold(0.0, |peak, sample| peak.max(sample.abs()))
+    }
+
+    #[test]
+    fn output_rate_matches() {
+        for input_rate in [44_100, 96_000] {
+            let mut resampler = Resampler::new(input_rate, 48_000);
+            let mut count: u32 = 0;
+            for _ in 0..input_rate {
+                resampler.process(0.0, |_| count += 1);
+            }
+            // give or take the filter's delay
+            assert!(count.abs_diff(48_000) < 32, "{input_rate}: {count}");
+        }
+    }
+
+    #[test]
+    fn keeps_amplitude_and_frequency() {
+        // the transition band starts to roll off above ~15kHz
+        for input_rate in [44_100, 96_000] {
+            for freq in [100.0, 1000.0, 10_000.0, 15_000.0] {
+                let output = resampled_sine(input_rate, freq, 0.5);
+                let amplitude = amplitude_at(&output, freq);
+                assert!(
+                    (amplitude - 0.5).abs() < 0.005,
+                    "{input_rate} {freq}Hz: amplitude {amplitude}"
+                );
+                // the energy sits at the tone, not at a shifted frequency
+                for off in [freq * 0.98, freq * 1.02] {
+                    let leak = amplitude_at(&output, off);
+                    assert!(leak < 0.01, "{input_rate} {freq}Hz leaks {leak} at {off}Hz");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn attenuates_above_output_nyquist() {
+        // these would alias down to 18kHz and 4kHz at 48kHz
+        for freq in [30_000.0, 44_000.0] {
+            let output = resampled_sine(96_000, freq, 0.5);
+            let alias = 48_000.0 - freq;
+            assert!(amplitude_at(&output, alias) < 0.001, "{freq}Hz");
+            assert!(peak(&output) < 0.005, "{freq}Hz: peak {}", peak(&output));
+        }
+        // upsampling mustn't leave images of the input spectrum, 20kHz would mirror
+        // around 22.05kHz to 24.1kHz and fold back to 23.9kHz
+        let output = resampled_sine(44_100, 20_000.0, 0.5);
+        assert!(amplitude_at(&output, 23_900.0) < 0.001);
+    }
+}
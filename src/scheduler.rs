@@ -0,0 +1,427 @@
+// Decides which animation of the rotation is on screen. Every animation plays for its
+// duration, then the next one is picked in order, shuffled or at random by weight.
+// The switch can wait for a bass onset or a change in loudness so it lands with the music.
+
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::audio_processing::AudioFeatures;
+use crate::config::Config;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum Order {
+    // As listed in [animations]
+    #[default]
+    Sequence,
+    // Every animation once per round in a random order, never the same twice in a row
+    Shuffle,
+    // Picked at random by weight, never the same twice in a row
+    Random,
+}
+
+impl FromStr for Order {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sequence" => Ok(Order::Sequence),
+            "shuffle" => Ok(Order::Shuffle),
+            "random" => Ok(Order::Random),
+            _ => Err(format!(
+                "Unknown order '{s}' (expected 'sequence', 'shuffle' or 'random')"
+            )),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum SwitchOn {
+    // As soon as the duration is over
+    #[default]
+    Time,
+    // On the next strong bass onset, e.g. a kick. The bar position isn't tracked,
+    // so this is any beat with a kick on it, not the downbeat.
+    Onset,
+    // Once the loudness jumps or drops, e.g. at a breakdown or drop
+    Energy,
+}
+
+impl FromStr for SwitchOn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "time" => Ok(SwitchOn::Time),
+            "onset" => Ok(SwitchOn::Onset),
+            "energy" => Ok(SwitchOn::Energy),
+            _ => Err(format!(
+                "Unknown switch_on '{s}' (expected 'time', 'onset' or 'energy')"
+            )),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ScheduleSettings {
+    pub order: Order,
+    pub switch_on: SwitchOn,
+    // Seconds to wait for an onset or energy change before switching anyway
+    pub max_wait: f32,
+}
+
+impl Default for ScheduleSettings {
+    fn default() -> Self {
+        ScheduleSettings {
+            order: Order::Sequence,
+            switch_on: SwitchOn::Time,
+            max_wait: 4.0,
+        }
+    }
+}
+
+// Bass onsets louder than this times the recent average count
+const ONSET_RATIO: f32 = 1.5;
+const BASS_AVERAGE_S: f32 = 1.0;
+// Onsets closer together than this are one onset
+const MIN_ONSET_INTERVAL_S: f32 = 0.25;
+// Loudness over the last half second against the last few seconds,
+// a ratio beyond ENERGY_RATIO either way is an energy change
+const ENERGY_SHORT_S: f32 = 0.5;
+const ENERGY_LONG_S: f32 = 6.0;
+const ENERGY_RATIO: f32 = 1.8;
+
+pub struct Scheduler {
+    settings: ScheduleSettings,
+    // Per animation, in the order of [animations]
+    durations: Vec<f32>,
+    weights: Vec<f32>,
+    current: usize,
+    started: f32,
+    last_update: f32,
+    // Shuffle: what is left of the current round
+    round: Vec<usize>,
+    rng: u64,
+
+    bass_average: f32,
+    last_bass: f32,
+    last_onset: f32,
+    energy_short: f32,
+    energy_long: f32,
+}
+
+impl Scheduler {
+    pub fn new(config: &Config) -> Scheduler {
+        let durations = config
+            .animations
+            .iter()
+            .map(|name| match config.animation_durations.get(name) {
+                Some(duration) => *duration,
+                None => config.animation_length as f32,
+            })
+            .collect();
+        let weights = config
+            .animations
+            .iter()
+            .map(|name| config.animation_weights.get(name).copied().unwrap_or(1.0))
+            .collect();
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        Scheduler::with_seed(config.schedule.clone(), durations, weights, seed)
+    }
+
+    fn with_seed(
+        settings: ScheduleSettings,
+        durations: Vec<f32>,
+        weights: Vec<f32>,
+        seed: u64,
+    ) -> Scheduler {
+        let mut scheduler = Scheduler {
+            settings,
+            durations,
+            weights,
+            current: 0,
+            started: 0.0,
+            last_update: 0.0,
+            round: vec![],
+            // xorshift gets stuck on 0
+            rng: seed | 1,
+            bass_average: 0.0,
+            last_bass: 0.0,
+            last_onset: 0.0,
+            energy_short: 0.0,
+            energy_long: 0.0,
+        };
+        if scheduler.settings.order != Order::Sequence {
+            scheduler.current = scheduler.next();
+        }
+        scheduler
+    }
+
+    // Advances to `now` seconds since startup, returns the animation to show
+    pub fn update(&mut self, now: f32, features: &AudioFeatures) -> usize {
+        let dt = (now - self.last_update).max(0.0);
+        self.last_update = now;
+        let onset = self.detect_onset(now, dt, features);
+        let energy_change = self.detect_energy_change(dt, features);
+
+        let playing = now - self.started;
+        let duration = self.durations[self.current];
+        if self.durations.len() < 2 || playing < duration {
+            return self.current;
+        }
+        let overdue = playing >= duration + self.settings.max_wait;
+        let switch = match self.settings.switch_on {
+            SwitchOn::Time => true,
+            SwitchOn::Onset => onset || overdue,
+            SwitchOn::Energy => energy_change || overdue,
+        };
+        if switch {
+            self.current = self.next();
+            self.started = now;
+        }
+        self.current
+    }
+
+    fn next(&mut self) -> usize {
+        let count = self.durations.len();
+        match self.settings.order {
+            Order::Sequence => (self.current + 1) % count,
+            Order::Shuffle => {
+                if self.round.is_empty() {
+                    self.round = (0..count).collect();
+                    // Fisher-Yates, the round is taken from the back
+                    for i in (1..count).rev() {
+                        let j = self.random_below(i + 1);
+                        self.round.swap(i, j);
+                    }
+                    // don't start the new round with what just played
+                    if count > 1 && self.round[count - 1] == self.current {
+                        self.round.swap(count - 1, 0);
+                    }
+                }
+                self.round.pop().unwrap_or(0)
+            }
+            Order::Random => {
+                let unit = self.random_unit();
+                let weight_of = |i: usize| {
+                    if i == self.current && count > 1 {
+                        0.0
+                    } else {
+                        self.weights[i]
+                    }
+                };
+                let total: f32 = (0..count).map(weight_of).sum();
+                let mut pick = unit * total;
+                for i in 0..count {
+                    pick -= weight_of(i);
+                    if pick < 0.0 {
+                        return i;
+                    }
+                }
+                // rounding left a sliver at the end, or every other weight is zero
+                (0..count)
+                    .rev()
+                    .find(|&i| weight_of(i) > 0.0)
+                    .unwrap_or(self.current)
+            }
+        }
+    }
+
+    fn detect_onset(&mut self, now: f32, dt: f32, features: &AudioFeatures) -> bool {
+        let bass = features.lo.smoothed_val * features.root_mean_squared.smoothed_val;
+        let rising = bass > self.last_bass;
+        self.last_bass = bass;
+        let onset = rising
+            && bass > self.bass_average * ONSET_RATIO
+            && now - self.last_onset >= MIN_ONSET_INTERVAL_S;
+        self.bass_average += (bass - self.bass_average) * smoothing(dt, BASS_AVERAGE_S);
+        if onset {
+            self.last_onset = now;
+        }
+        onset
+    }
+
+    fn detect_energy_change(&mut self, dt: f32, features: &AudioFeatures) -> bool {
+        let energy = features.root_mean_squared.smoothed_val;
+        self.energy_short += (energy - self.energy_short) * smoothing(dt, ENERGY_SHORT_S);
+        self.energy_long += (energy - self.energy_long) * smoothing(dt, ENERGY_LONG_S);
+        if self.energy_short <= 0.0 || self.energy_long <= 0.0 {
+            return false;
+        }
+        let ratio = self.energy_short / self.energy_long;
+        !(1.0 / ENERGY_RATIO..=ENERGY_RATIO).contains(&ratio)
+    }
+
+    // xorshift64*, plenty for picking animations
+    fn random_u64(&mut self) -> u64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // In [0, 1)
+    fn random_unit(&mut self) -> f32 {
+        (self.random_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn random_below(&mut self, n: usize) -> usize {
+        (self.random_u64() % n as u64) as usize
+    }
+}
+
+// Weight of the newest value in an exponential average with time constant `tau`
+fn smoothing(dt: f32, tau: f32) -> f32 {
+    1.0 - (-dt / tau).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+    fn scheduler(
+        order: Order,
+        switch_on: SwitchOn,
+        durations: &[f32],
+        weights: &[f32],
+    ) -> Scheduler {
+        let settings = ScheduleSettings {
+            order,
+            switch_on,
+            max_wait: 2.0,
+        };
+        Scheduler::with_seed(settings, durations.to_vec(), weights.to_vec(), SEED)
+    }
+
+    fn features(bass: f32, loudness: f32) -> AudioFeatures {
+        let mut features = AudioFeatures::default();
+        features.lo.smoothed_val = bass;
+        features.root_mean_squared.smoothed_val = loudness;
+        features
+    }
+
+    // Steps through [from, to) at 60fps, returns when the animation changed
+    fn run(
+        scheduler: &mut Scheduler,
+        from: f32,
+        to: f32,
+        features: impl Fn(f32) -> AudioFeatures,
+    ) -> Option<f32> {
+        let before = scheduler.current;
+        let mut now = from;
+        while now < to {
+            if scheduler.update(now, &features(now)) != before {
+                return Some(now);
+            }
+            now += 1.0 / 60.0;
+        }
+        None
+    }
+
+    #[test]
+    fn sequence_plays_each_for_its_duration() {
+        let mut scheduler = scheduler(Order::Sequence, SwitchOn::Time, &[1.0, 2.0, 0.5], &[]);
+        let silence = |_| AudioFeatures::default();
+        let first = run(&mut scheduler, 0.0, 10.0, silence).unwrap();
+        assert!((1.0..1.02).contains(&first));
+        assert_eq!(scheduler.current, 1);
+        let second = run(&mut scheduler, first, 10.0, silence).unwrap();
+        assert!((first + 2.0..first + 2.02).contains(&second));
+        let third = run(&mut scheduler, second, 10.0, silence).unwrap();
+        assert!((second + 0.5..second + 0.52).contains(&third));
+        assert_eq!(scheduler.current, 0);
+    }
+
+    #[test]
+    fn shuffle_plays_everything_once_per_round_without_repeats() {
+        let mut scheduler = scheduler(Order::Shuffle, SwitchOn::Time, &[1.0; 5], &[]);
+        let mut picks = vec![scheduler.current];
+        for _ in 0..499 {
+            scheduler.current = scheduler.next();
+            picks.push(scheduler.current);
+        }
+        for round in picks.chunks(5) {
+            let mut sorted = round.to_vec();
+            sorted.sort();
+            assert_eq!(sorted, [0, 1, 2, 3, 4]);
+        }
+        // including where one round ends and the next starts
+        assert!(picks.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn random_follows_weights_and_skips_weight_zero() {
+        let mut scheduler = scheduler(
+            Order::Random,
+            SwitchOn::Time,
+            &[1.0; 4],
+            &[1.0, 0.0, 3.0, 1.0],
+        );
+        let mut counts = [0; 4];
+        let mut last = scheduler.current;
+        for _ in 0..4000 {
+            scheduler.current = scheduler.next();
+            assert_ne!(scheduler.current, last);
+            last = scheduler.current;
+            counts[scheduler.current] += 1;
+        }
+        assert_eq!(counts[1], 0);
+        assert!(counts[2] > counts[0] && counts[2] > counts[3], "{counts:?}");
+    }
+
+    #[test]
+    fn random_stays_put_when_nothing_else_has_weight() {
+        let mut scheduler = scheduler(Order::Random, SwitchOn::Time, &[1.0; 3], &[0.0, 0.0, 1.0]);
+        assert_eq!(scheduler.current, 2);
+        for _ in 0..100 {
+            scheduler.current = scheduler.next();
+            assert_eq!(scheduler.current, 2);
+        }
+    }
+
+    #[test]
+    fn onset_switches_on_a_bass_hit() {
+        let mut scheduler = scheduler(Order::Sequence, SwitchOn::Onset, &[1.0, 1.0], &[]);
+        // steady bass, then a kick 1.5s in
+        let kick = |now: f32| {
+            if (1.5..1.6).contains(&now) {
+                features(1.0, 1.0)
+            } else {
+                features(0.2, 0.2)
+            }
+        };
+        let switched = run(&mut scheduler, 0.0, 10.0, kick).unwrap();
+        assert!((1.5..1.52).contains(&switched), "{switched}");
+    }
+
+    #[test]
+    fn onset_and_energy_fall_back_after_max_wait() {
+        for switch_on in [SwitchOn::Onset, SwitchOn::Energy] {
+            // long enough for the loudness averages to settle on the steady level
+            let mut scheduler = scheduler(Order::Sequence, switch_on, &[8.0, 8.0], &[]);
+            let steady = |_| features(0.2, 0.2);
+            let switched = run(&mut scheduler, 0.0, 20.0, steady).unwrap();
+            assert!(
+                (10.0..10.02).contains(&switched),
+                "{switch_on:?}: {switched}"
+            );
+        }
+    }
+
+    #[test]
+    fn energy_switches_on_a_drop() {
+        let mut scheduler = scheduler(Order::Sequence, SwitchOn::Energy, &[5.0, 5.0], &[]);
+        let drop = |now: f32| {
+            if now < 5.5 {
+                features(0.5, 0.5)
+            } else {
+                features(0.01, 0.01)
+            }
+        };
+        let switched = run(&mut scheduler, 0.0, 10.0, drop).unwrap();
+        assert!((5.5..7.0).contains(&switched), "{switched}");
+    }
+}
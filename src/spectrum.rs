@@ -0,0 +1,177 @@
+// Implements a multi-resolution (constant-Q like) spectrum as an alternative to linear FFT bins.
+//
+// Several FFT sizes are run over the same sample history and stitched together per octave:
+// long windows give the bass enough frequency resolution, short windows keep the treble responsive.
+// The result is a set of log-spaced bands, which is how music is usually perceived.
+
+use rustfft::{num_complex::Complex, FftPlanner};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::audio_processing::SPECTRUM_SIZE;
+
+// FFT sizes, largest first. Each size covers one octave above the previous crossover.
+const FFT_SIZES: [usize; 5] = [8192, 4096, 2048, 1024, 512];
+// Lowest frequency handled by each FFT size (the largest FFT also covers everything below).
+const CROSSOVERS: [f32; 5] = [0.0, 160.0, 320.0, 640.0, 1280.0];
+const MIN_FREQ: f32 = 20.0;
+const MAX_FREQ: f32 = 20_000.0;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum SpectrumSource {
+    // Linear FFT bins straight from `compute_fft`
+    #[default]
+    Fft,
+    // Log-spaced bands stitched from several FFT sizes
+    MultiResolution,
+}
+
+impl FromStr for SpectrumSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fft" => Ok(SpectrumSource::Fft),
+            "multi-resolution" | "multi_resolution" | "constant-q" | "cqt" => {
+                Ok(SpectrumSource::MultiResolution)
+            }
+            _ => Err(format!(
+                "Unknown spectrum source '{s}' (expected 'fft' or 'multi-resolution')"
+            )),
+        }
+    }
+}
+
+// Precomputed mapping from one output band to the bins of one of the FFTs
+#[derive(Copy, Clone)]
+struct BandMapping {
+    resolution: usize,
+    bin_lo: usize,
+    bin_hi: usize,
+    // fractional bin position of the band center, used when the band is narrower than a bin
+    center_bin: f32,
+}
+
+pub struct MultiResolutionSpectrum {
+    ffts: Vec<Arc<dyn rustfft::Fft<f32>>>,
+    windows: Vec<Vec<f32>>,
+    history: Vec<f32>,
+    head: usize,
+    band_freqs: [f32; SPECTRUM_SIZE],
+    bands: Vec<BandMapping>,
+    scratch: Vec<Complex<f32>>,
+    magnitudes: Vec<Vec<f32>>,
+}
+
+impl MultiResolutionSpectrum {
+    pub fn new(sample_rate: f32) -> MultiResolutionSpectrum {
+        let mut planner = FftPlanner::new();
+        let ffts = FFT_SIZES
+            .iter()
+            .map(|size| planner.plan_fft_forward(*size))
+            .collect();
+        let windows = FFT_SIZES.iter().map(|size| hann_window(*size)).collect();
+
+        // log-spaced band centers
+        let max_freq = MAX_FREQ.min(sample_rate / 2.0);
+        let octaves = (max_freq / MIN_FREQ).log2();
+        let mut band_freqs = [0.0; SPECTRUM_SIZE];
+        for (i, freq) in band_freqs.iter_mut().enumerate() {
+            *freq = MIN_FREQ * 2f32.powf(octaves * (i as f32) / (SPECTRUM_SIZE as f32));
+        }
+
+        // each band spans half the distance to its neighbours
+        let half_step = 2f32.powf(octaves / (SPECTRUM_SIZE as f32) / 2.0);
+        let bands = band_freqs
+            .iter()
+            .map(|freq| {
+                let resolution = CROSSOVERS
+                    .iter()
+                    .rposition(|crossover| *freq >= *crossover)
+                    .unwrap_or(0);
+                let bin_width = sample_rate / (FFT_SIZES[resolution] as f32);
+                let max_bin = FFT_SIZES[resolution] / 2 - 1;
+                BandMapping {
+                    resolution,
+                    bin_lo: ((freq / half_step / bin_width).ceil() as usize).min(max_bin),
+                    bin_hi: ((freq * half_step / bin_width).floor() as usize).min(max_bin),
+                    center_bin: (freq / bin_width).min(max_bin as f32),
+                }
+            })
+            .collect();
+
+        MultiResolutionSpectrum {
+            ffts,
+            windows,
+            history: vec![0.0; FFT_SIZES[0]],
+            head: 0,
+            band_freqs,
+            bands,
+            scratch: vec![Complex { re: 0.0, im: 0.0 }; FFT_SIZES[0]],
+            magnitudes: FFT_SIZES.iter().map(|size| vec![0.0; size / 2]).collect(),
+        }
+    }
+
+    pub fn band_freqs(&self) -> &[f32; SPECTRUM_SIZE] {
+        &self.band_freqs
+    }
+
+    pub fn push(&mut self, value: f32) {
+        self.history[self.head] = value;
+        self.head = (self.head + 1) % self.history.len();
+    }
+
+    // Returns band magnitudes scaled to be comparable with the plain FFT, before normalization
+    pub fn compute(&mut self, reference_size: usize) -> [f32; SPECTRUM_SIZE] {
+        let history_len = self.history.len();
+        for (resolution, size) in FFT_SIZES.iter().enumerate() {
+            // copy the most recent `size` samples, oldest first, through the window
+            let start = (self.head + history_len - size) % history_len;
+            let scratch = &mut self.scratch[0..*size];
+            for (i, (bin, w)) in scratch
+                .iter_mut()
+                .zip(self.windows[resolution].iter())
+                .enumerate()
+            {
+                *bin = Complex {
+                    re: self.history[(start + i) % history_len] * w,
+                    im: 0.0,
+                };
+            }
+            self.ffts[resolution].process(scratch);
+
+            // hann window halves the amplitude, rescale to the reference (unwindowed) FFT size
+            let scale = 2.0 * (reference_size as f32) / (*size as f32);
+            for (mag, bin) in self.magnitudes[resolution].iter_mut().zip(scratch.iter()) {
+                *mag = bin.norm() * scale;
+            }
+        }
+
+        let mut result = [0.0; SPECTRUM_SIZE];
+        for (value, band) in result.iter_mut().zip(self.bands.iter()) {
+            let magnitudes = &self.magnitudes[band.resolution];
+            *value = if band.bin_lo <= band.bin_hi {
+                // band spans at least one bin, take the strongest
+                magnitudes[band.bin_lo..=band.bin_hi]
+                    .iter()
+                    .fold(0.0, |max, mag| mag.max(max))
+            } else {
+                // band is narrower than a bin, interpolate between neighbours
+                let lo = band.center_bin.floor() as usize;
+                let hi = (lo + 1).min(magnitudes.len() - 1);
+                let frac = band.center_bin - (lo as f32);
+                magnitudes[lo] * (1.0 - frac) + magnitudes[hi] * frac
+            };
+        }
+        result
+    }
+}
+
+fn hann_window(size: usize) -> Vec<f32> {
+    (0..size)
+        .map(|i| {
+            let phase = 2.0 * std::f32::consts::PI * (i as f32) / (size as f32);
+            0.5 * (1.0 - phase.cos())
+        })
+        .collect()
+}
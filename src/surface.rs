@@ -0,0 +1,140 @@
+// A rectangle of colored cells that animators draw into. The drawing helpers are
+// built on get_cell/set_cell, so anything that stores cells can be rendered to.
+
+use crate::colors::Color;
+use crate::terminal_grid::ColoredChar;
+
+// A cell that lets whatever is below show through when layers are stacked,
+// drawn as the background otherwise
+pub const NO_CELL: char = '\0';
+
+pub trait Surface {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+    fn bg_color(&self) -> Color;
+    fn get_cell(&self, x: usize, y: usize) -> ColoredChar;
+    // Writes outside the surface are ignored
+    fn set_cell(&mut self, c: char, color: Color, x: usize, y: usize);
+
+    fn draw_line_h(&mut self, c: char, color: Color, x: usize, y: usize, len: i32) {
+        if len < 0 {
+            let len = -len as usize;
+            self.draw_box(c, color, x.saturating_sub(len), y, len, 1);
+        } else {
+            self.draw_box(c, color, x, y, len as usize, 1);
+        }
+    }
+
+    fn draw_line_v(&mut self, c: char, color: Color, x: usize, y: usize, len: i32) {
+        if len < 0 {
+            let len = -len as usize;
+            self.draw_box(c, color, x, y.saturating_sub(len), 1, len);
+        } else {
+            self.draw_box(c, color, x, y, 1, len as usize);
+        }
+    }
+
+    fn draw_box(&mut self, c: char, color: Color, x: usize, y: usize, w: usize, h: usize) {
+        for i in x..(x + w).min(self.width()) {
+            for j in y..(y + h).min(self.height()) {
+                self.set_cell(c, color, i, j);
+            }
+        }
+    }
+
+    // Writes text left to right from (x, y), cut off at the right edge
+    fn draw_text(&mut self, text: &str, color: Color, x: usize, y: usize) {
+        if y >= self.height() {
+            return;
+        }
+        for (i, c) in text
+            .chars()
+            .enumerate()
+            .take(self.width().saturating_sub(x))
+        {
+            self.set_cell(c, color, x + i, y);
+        }
+    }
+
+    fn clear(&mut self) {
+        let bg = self.bg_color();
+        self.fill(' ', bg);
+    }
+
+    fn fill(&mut self, c: char, color: Color) {
+        let (w, h) = (self.width(), self.height());
+        self.draw_box(c, color, 0, 0, w, h);
+    }
+
+    // Blends every cell's color towards the background, 0.0 leaves it unchanged
+    fn dim(&mut self, amount: f32) {
+        let bg = self.bg_color();
+        let blend = |c: u8, bg: u8| ((c as f32) + ((bg as f32) - (c as f32)) * amount) as u8;
+        for x in 0..self.width() {
+            for y in 0..self.height() {
+                let cell = self.get_cell(x, y);
+                let color = (
+                    blend(cell.color.0, bg.0),
+                    blend(cell.color.1, bg.1),
+                    blend(cell.color.2, bg.2),
+                );
+                self.set_cell(cell.c, color, x, y);
+            }
+        }
+    }
+}
+
+// A rectangle of another surface with its own coordinates, e.g. one region of a layout.
+// Drawing is clipped to the rectangle.
+pub struct Viewport<'a> {
+    surface: &'a mut dyn Surface,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl<'a> Viewport<'a> {
+    pub fn new(
+        surface: &'a mut dyn Surface,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Viewport<'a> {
+        let width = width.min(surface.width().saturating_sub(x));
+        let height = height.min(surface.height().saturating_sub(y));
+        Viewport {
+            surface,
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+impl Surface for Viewport<'_> {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn bg_color(&self) -> Color {
+        self.surface.bg_color()
+    }
+
+    fn get_cell(&self, x: usize, y: usize) -> ColoredChar {
+        self.surface.get_cell(self.x + x, self.y + y)
+    }
+
+    fn set_cell(&mut self, c: char, color: Color, x: usize, y: usize) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        self.surface.set_cell(c, color, self.x + x, self.y + y);
+    }
+}
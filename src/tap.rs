@@ -0,0 +1,239 @@
+// Tees the samples the input delivers into a 16 bit mono WAV, either continuously
+// up to a limit, or as a rolling window of the last seconds that is saved on request.
+// Backends push a mono downmix, so the device's channels and sample format are not kept.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::audio_formats::SampleFormat;
+
+const WAV_FORMAT: SampleFormat = SampleFormat::S16LE;
+const WAV_HEADER_SIZE: u32 = 44;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum TapMode {
+    #[default]
+    Off,
+    // Write everything from startup until the limit is reached
+    File,
+    // Keep the last `max_seconds` in memory, saved to a new file on request
+    Rolling,
+}
+
+impl FromStr for TapMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(TapMode::Off),
+            "file" => Ok(TapMode::File),
+            "rolling" => Ok(TapMode::Rolling),
+            _ => Err(format!(
+                "Unknown capture mode '{s}' (expected 'off', 'file' or 'rolling')"
+            )),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TapSettings {
+    pub mode: TapMode,
+    // Rolling dumps insert a timestamp before the extension
+    pub path: String,
+    pub max_seconds: f32,
+    // Upper bound on the file size in file mode
+    pub max_bytes: Option<u64>,
+}
+
+impl Default for TapSettings {
+    fn default() -> Self {
+        TapSettings {
+            mode: TapMode::Off,
+            path: String::from("capture.wav"),
+            max_seconds: 30.0,
+            max_bytes: None,
+        }
+    }
+}
+
+pub struct AudioTap {
+    settings: TapSettings,
+    // File mode: samples waiting to be handed to the writer thread
+    pending: Vec<f32>,
+    writer: Option<mpsc::Sender<(u32, Vec<f32>)>>,
+    written: usize,
+    // Rolling mode
+    history: VecDeque<f32>,
+    // Failures of the writer threads, shown by the UI since the terminal is taken
+    errors: mpsc::Receiver<String>,
+    error_sender: mpsc::Sender<String>,
+}
+
+impl AudioTap {
+    pub fn new(settings: TapSettings) -> Result<AudioTap, anyhow::Error> {
+        let (error_sender, errors) = mpsc::channel();
+        let writer = match settings.mode {
+            TapMode::File => Some(spawn_writer(&settings.path, error_sender.clone())?),
+            TapMode::Off | TapMode::Rolling => None,
+        };
+        Ok(AudioTap {
+            settings,
+            pending: vec![],
+            writer,
+            written: 0,
+            history: VecDeque::new(),
+            errors,
+            error_sender,
+        })
+    }
+
+    pub fn push(&mut self, sample: f32, sample_rate: u32) {
+        let max_samples = (self.settings.max_seconds * sample_rate as f32) as usize;
+        match self.settings.mode {
+            TapMode::Off => {}
+            TapMode::File => {
+                let max_bytes = self.settings.max_bytes.unwrap_or(u64::MAX);
+                let max_samples =
+                    max_samples.min((max_bytes / WAV_FORMAT.bytes_per_sample() as u64) as usize);
+                if self.written + self.pending.len() >= max_samples {
+                    // Dropping the sender ends the writer once it has written everything
+                    self.flush(sample_rate);
+                    self.writer = None;
+                    return;
+                }
+                self.pending.push(sample);
+                // Hand over about every 100ms
+                if self.pending.len() >= (sample_rate / 10) as usize {
+                    self.flush(sample_rate);
+                }
+            }
+            TapMode::Rolling => {
+                self.history.push_back(sample);
+                while self.history.len() > max_samples {
+                    self.history.pop_front();
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self, sample_rate: u32) {
+        if let Some(writer) = &self.writer {
+            self.written += self.pending.len();
+            let _ = writer.send((sample_rate, std::mem::take(&mut self.pending)));
+        }
+    }
+
+    // Saves the rolling window in the background, returns the file it is written to
+    pub fn dump(&self, sample_rate: u32) -> Option<String> {
+        if self.settings.mode != TapMode::Rolling {
+            return None;
+        }
+        let path = timestamped_path(&self.settings.path);
+        let samples: Vec<f32> = self.history.iter().copied().collect();
+        let dump_path = path.clone();
+        let errors = self.error_sender.clone();
+        thread::spawn(move || {
+            let written =
+                WavWriter::create(&dump_path, sample_rate).and_then(|mut wav| wav.write(&samples));
+            if let Err(err) = written {
+                let _ = errors.send(format!("Failed to save capture to '{dump_path}': {err}"));
+            }
+        });
+        Some(path)
+    }
+
+    // The oldest failure of a background write not reported yet
+    pub fn take_error(&self) -> Option<String> {
+        self.errors.try_recv().ok()
+    }
+}
+
+fn spawn_writer(
+    path: &str,
+    errors: mpsc::Sender<String>,
+) -> Result<mpsc::Sender<(u32, Vec<f32>)>, anyhow::Error> {
+    // Create the file up front so a bad path is reported at startup
+    File::create(path)
+        .map_err(|err| anyhow::Error::msg(format!("Failed to create '{path}': {err}")))?;
+    let (sender, receiver) = mpsc::channel::<(u32, Vec<f32>)>();
+    let path = path.to_string();
+    thread::spawn(move || {
+        let mut wav: Option<WavWriter> = None;
+        for (sample_rate, samples) in receiver {
+            // The header needs the rate, which the backend only knows once connected
+            let written = match wav.as_mut() {
+                Some(wav) => wav.write(&samples),
+                None => WavWriter::create(&path, sample_rate).and_then(|mut new_wav| {
+                    new_wav.write(&samples)?;
+                    wav = Some(new_wav);
+                    Ok(())
+                }),
+            };
+            if let Err(err) = written {
+                let _ = errors.send(format!("Failed to write capture to '{path}': {err}"));
+                return;
+            }
+        }
+    });
+    Ok(sender)
+}
+
+// capture.wav -> capture-<unix time>.wav
+fn timestamped_path(path: &str) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    match path.rsplit_once('.') {
+        Some((stem, extension)) => format!("{stem}-{timestamp}.{extension}"),
+        None => format!("{path}-{timestamp}"),
+    }
+}
+
+// Streams a mono WAV, the header sizes are patched after every write so
+// the file stays playable if the program is killed
+struct WavWriter {
+    file: BufWriter<File>,
+    data_len: u32,
+}
+
+impl WavWriter {
+    fn create(path: &str, sample_rate: u32) -> std::io::Result<WavWriter> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let bytes_per_sample = WAV_FORMAT.bytes_per_sample() as u32;
+        file.write_all(b"RIFF")?;
+        file.write_all(&(WAV_HEADER_SIZE - 8).to_le_bytes())?;
+        file.write_all(b"WAVEfmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        // PCM, mono
+        file.write_all(&1u16.to_le_bytes())?;
+        file.write_all(&1u16.to_le_bytes())?;
+        file.write_all(&sample_rate.to_le_bytes())?;
+        file.write_all(&(sample_rate * bytes_per_sample).to_le_bytes())?;
+        file.write_all(&(bytes_per_sample as u16).to_le_bytes())?;
+        file.write_all(&(8 * bytes_per_sample as u16).to_le_bytes())?;
+        file.write_all(b"data")?;
+        file.write_all(&0u32.to_le_bytes())?;
+        Ok(WavWriter { file, data_len: 0 })
+    }
+
+    fn write(&mut self, samples: &[f32]) -> std::io::Result<()> {
+        let mut bytes = Vec::with_capacity(samples.len() * WAV_FORMAT.bytes_per_sample());
+        WAV_FORMAT.encode(samples, &mut bytes);
+        self.file.write_all(&bytes)?;
+        self.data_len += bytes.len() as u32;
+
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file
+            .write_all(&(WAV_HEADER_SIZE - 8 + self.data_len).to_le_bytes())?;
+        self.file
+            .seek(SeekFrom::Start(WAV_HEADER_SIZE as u64 - 4))?;
+        self.file.write_all(&self.data_len.to_le_bytes())?;
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.flush()
+    }
+}
@@ -1,4 +1,5 @@
 use crate::colors::{Color, BLOCK_CHAR};
+use crate::surface::{Surface, NO_CELL};
 use ansi_term::Color::RGB;
 use ansi_term::{ANSIByteStrings, ANSIGenericString, Style};
 
@@ -18,8 +19,11 @@ pub struct TerminalGrid {
 impl TerminalGrid {
     pub fn new(bg_color: Color) -> TerminalGrid {
         let (w, h) = crossterm::terminal::size().unwrap();
-        let w = w as usize;
-        let h = h as usize;
+        TerminalGrid::with_size(bg_color, w as usize, h as usize)
+    }
+
+    // A grid that isn't tied to the terminal size, e.g. one pane of a split screen
+    pub fn with_size(bg_color: Color, w: usize, h: usize) -> TerminalGrid {
         let grid_size = w * h;
         TerminalGrid {
             grid: vec![
@@ -43,21 +47,31 @@ impl TerminalGrid {
         }
     }
 
-    pub fn index_2d(self: &TerminalGrid, i: usize, j: usize) -> usize {
-        j * self.width + i
-    }
-
-    pub fn set_cell(self: &mut TerminalGrid, c: char, color: Color, i: usize, j: usize) {
-        let t = self.index_2d(i, j);
-        if t > self.grid_size {
-            println!("sdfsdf");
+    pub fn resize(self: &mut TerminalGrid, w: usize, h: usize) {
+        if w == self.width && h == self.height {
             return;
         }
-        self.grid[t] = ColoredChar { c, color };
+        self.width = w;
+        self.height = h;
+        self.grid_size = w * h;
+        self.grid.resize(
+            self.grid_size,
+            ColoredChar {
+                c: ' ',
+                color: self.bg_color,
+            },
+        );
+        self.last_grid = vec![
+            ColoredChar {
+                c: '\n',
+                color: (0, 0, 0)
+            };
+            self.grid.len()
+        ];
     }
 
-    pub fn get_cell(self: &TerminalGrid, i: usize, j: usize) -> ColoredChar {
-        self.grid[self.index_2d(i, j)]
+    pub fn index_2d(self: &TerminalGrid, i: usize, j: usize) -> usize {
+        j * self.width + i
     }
 
     pub fn get_line(self: &TerminalGrid, j: usize) -> String {
@@ -84,90 +98,10 @@ impl TerminalGrid {
         result
     }
 
-    pub fn draw_line_h(
-        self: &mut TerminalGrid,
-        c: char,
-        color: Color,
-        x: usize,
-        y: usize,
-        len: i32,
-    ) {
-        if len < 0 {
-            let len = -len as usize;
-            self.draw_box(c, color, x - len, y, len, 1);
-        } else {
-            self.draw_box(c, color, x, y, len as usize, 1);
-        }
-    }
-
-    pub fn draw_line_v(
-        self: &mut TerminalGrid,
-        c: char,
-        color: Color,
-        x: usize,
-        y: usize,
-        len: i32,
-    ) {
-        if len < 0 {
-            let len = -len as usize;
-            self.draw_box(c, color, x, y - len, 1, len);
-        } else {
-            self.draw_box(c, color, x, y, 1, len as usize);
-        }
-    }
-
-    pub fn draw_box(
-        self: &mut TerminalGrid,
-        c: char,
-        color: Color,
-        x: usize,
-        y: usize,
-        w: usize,
-        h: usize,
-    ) {
-        for i in x..(x + w).min(self.width) {
-            for j in y..(y + h).min(self.height) {
-                self.set_cell(c, color, i, j);
-            }
-        }
-    }
-
-    pub fn clear(self: &mut TerminalGrid) {
-        self.fill(' ', self.bg_color);
-    }
-
-    pub fn fill(self: &mut TerminalGrid, c: char, color: Color) {
-        for i in 0..self.width {
-            for j in 0..self.height {
-                self.set_cell(c, color, i, j);
-            }
-        }
-    }
-
     pub fn display(self: &mut TerminalGrid) {
         // Resize char buffer if needed
         let (w, h) = crossterm::terminal::size().unwrap();
-        let w = w as usize;
-        let h = h as usize;
-        if w != self.width || h != self.height {
-            self.width = w;
-            self.height = h;
-            self.grid_size = w * h;
-            self.grid.resize(
-                self.grid_size,
-                ColoredChar {
-                    c: ' ',
-                    color: self.bg_color,
-                },
-            );
-            self.last_grid = vec![
-                ColoredChar {
-                    c: '\n',
-                    color: (0, 0, 0)
-                };
-                self.grid.len()
-            ];
-        }
+        self.resize(w as usize, h as usize);
 
         // Detect diffs
         let mut diffs: Vec<(usize, usize, ColoredChar)> = vec![];
@@ -211,6 +145,32 @@ impl TerminalGrid {
     }
 }
 
+impl Surface for TerminalGrid {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn bg_color(&self) -> Color {
+        self.bg_color
+    }
+
+    fn get_cell(&self, x: usize, y: usize) -> ColoredChar {
+        self.grid[self.index_2d(x, y)]
+    }
+
+    fn set_cell(&mut self, c: char, color: Color, x: usize, y: usize) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let idx = self.index_2d(x, y);
+        self.grid[idx] = ColoredChar { c, color };
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct ColoredChar {
     pub c: char,
@@ -225,11 +185,19 @@ impl ColoredChar {
         }
     }
     pub fn to_string(self: &ColoredChar, bg_color: Color) -> String {
-        self.painted(bg_color).paint(self.c.to_string()).to_string()
+        self.painted(bg_color)
+            .paint(self.visible().to_string())
+            .to_string()
     }
     pub fn to_ansi(self: &ColoredChar, bg_color: Color) -> ANSIGenericString<'_, [u8]> {
         self.painted(bg_color)
-            .paint(self.c.to_string().as_bytes().to_owned())
+            .paint(self.visible().to_string().as_bytes().to_owned())
+    }
+    fn visible(self) -> char {
+        match self.c {
+            NO_CELL => ' ',
+            c => c,
+        }
     }
     fn painted(self, bg_color: Color) -> Style {
         let color = RGB(self.color.0, self.color.1, self.color.2);
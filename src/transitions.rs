@@ -0,0 +1,95 @@
+// Blends the outgoing animation into the incoming one when the rotation moves on.
+// Both animations keep running during the transition, each drawn into its own surface.
+
+use std::str::FromStr;
+
+use crate::colors::lerp;
+use crate::surface::Surface;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum Transition {
+    // Switch on the next frame
+    #[default]
+    Cut,
+    // Interpolate the colors, cells that change character pass through the background
+    Crossfade,
+    // The new animation is revealed from left to right
+    Wipe,
+    // Cells switch over one by one in a scattered order
+    Dissolve,
+    // The new animation pushes the old one out to the left
+    Slide,
+}
+
+impl FromStr for Transition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cut" => Ok(Transition::Cut),
+            "crossfade" => Ok(Transition::Crossfade),
+            "wipe" => Ok(Transition::Wipe),
+            "dissolve" => Ok(Transition::Dissolve),
+            "slide" => Ok(Transition::Slide),
+            _ => Err(format!(
+                "Unknown transition '{s}' (expected 'cut', 'crossfade', 'wipe', 'dissolve' or 'slide')"
+            )),
+        }
+    }
+}
+
+impl Transition {
+    // Draws the mix of two equally sized surfaces into `target`,
+    // `progress` runs from 0.0 (only `from`) to 1.0 (only `to`)
+    pub fn blend(
+        self,
+        from: &dyn Surface,
+        to: &dyn Surface,
+        progress: f32,
+        target: &mut dyn Surface,
+    ) {
+        let width = target.width().min(from.width()).min(to.width());
+        let height = target.height().min(from.height()).min(to.height());
+        let progress = progress.clamp(0.0, 1.0);
+        let bg = target.bg_color();
+        // columns of `to` that are on screen, for wipe and slide
+        let shown = ((width as f32) * progress).round() as usize;
+
+        for x in 0..width {
+            for y in 0..height {
+                let cell = match self {
+                    Transition::Cut => to.get_cell(x, y),
+                    Transition::Crossfade => {
+                        let (old, new) = (from.get_cell(x, y), to.get_cell(x, y));
+                        let mut cell = new;
+                        if old.c == new.c {
+                            cell.color = lerp(old.color, new.color, progress);
+                        } else if progress < 0.5 {
+                            cell = old;
+                            cell.color = lerp(old.color, bg, progress * 2.0);
+                        } else {
+                            cell.color = lerp(bg, new.color, progress * 2.0 - 1.0);
+                        }
+                        cell
+                    }
+                    Transition::Wipe if x < shown => to.get_cell(x, y),
+                    Transition::Wipe => from.get_cell(x, y),
+                    Transition::Dissolve if scatter(x, y) < progress => to.get_cell(x, y),
+                    Transition::Dissolve => from.get_cell(x, y),
+                    Transition::Slide if x + shown < width => from.get_cell(x + shown, y),
+                    Transition::Slide => to.get_cell(x + shown - width, y),
+                };
+                target.set_cell(cell.c, cell.color, x, y);
+            }
+        }
+    }
+}
+
+// A fixed pseudo random value in [0, 1) per cell, so dissolving cells don't flicker
+fn scatter(x: usize, y: usize) -> f32 {
+    let mut hash = (x as u32).wrapping_mul(0x9E37_79B1) ^ (y as u32).wrapping_mul(0x85EB_CA77);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x2C1B_3C6D);
+    hash ^= hash >> 12;
+    (hash >> 8) as f32 / (1 << 24) as f32
+}